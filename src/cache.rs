@@ -0,0 +1,337 @@
+//! Caching the projects found in the source directories between runs.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+
+use crate::config::{home_dir, SrcDir};
+use crate::scan::{Project, Scan, ScanOptions};
+use crate::{path_from_bytes, PROJECT_NAME};
+
+/// Returns `$XDG_CACHE_HOME`, falling back to `~/.cache` when it's unset, empty or not an
+/// absolute path as per the XDG Base Directory Specification.
+pub fn cache_home() -> Result<PathBuf> {
+    match env::var_os("XDG_CACHE_HOME").map(PathBuf::from) {
+        Some(path) if path.is_absolute() => Ok(path),
+        _ => Ok(home_dir()?.join(".cache")),
+    }
+}
+
+/// Returns the path of the `name` file in this program's directory of `$XDG_CACHE_HOME`, where
+/// everything it generates is kept.
+///
+/// The directory isn't created until something is written with [`write_cache_file`].
+pub fn cache_path(name: &str) -> Result<PathBuf> {
+    Ok(cache_home()?.join(PROJECT_NAME).join(name))
+}
+
+/// Writes `contents` to the cache file at `path`, creating its directory if needed.
+///
+/// The directory is only readable by the user on Unix, since the cache reveals which projects
+/// they have.
+pub fn write_cache_file(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(dir)?;
+    }
+    fs::write(path, contents)?;
+
+    Ok(())
+}
+
+/// The first line of a project cache file, changed whenever the format changes.
+const CACHE_HEADER: &[u8] = b"find_project cache v3";
+
+/// Returns the key identifying a scan of `src_dirs`, so that changing the config or options
+/// invalidates the cache.
+///
+/// The key is an FNV-1a hash of the settings that change which projects are found, so unlike
+/// `DefaultHasher` it stays the same between Rust releases.
+pub fn cache_key(src_dirs: &[SrcDir], opts: &ScanOptions) -> u64 {
+    // Destructured without `..`, so that adding a setting fails to compile until it's decided
+    // whether it changes the scan
+    let ScanOptions {
+        hidden,
+        exclude_ignore_case,
+        follow_symlinks,
+        git_roots,
+    } = *opts;
+    let mut key = vec![
+        u8::from(hidden),
+        u8::from(exclude_ignore_case),
+        u8::from(follow_symlinks),
+        u8::from(git_roots),
+    ];
+
+    for src_dir in src_dirs {
+        let SrcDir {
+            path,
+            min_depth,
+            max_depth,
+            markers,
+            exclude,
+            search_only,
+            startup: _,
+            layout: _,
+            session_name: _,
+        } = src_dir;
+        push_key_field(&mut key, path.as_os_str().as_encoded_bytes());
+        key.extend([*min_depth, *max_depth]);
+        for names in [markers, exclude, search_only] {
+            key.extend((names.len() as u64).to_le_bytes());
+            for name in names {
+                push_key_field(&mut key, name.as_bytes());
+            }
+        }
+    }
+
+    fnv1a(&key)
+}
+
+/// Adds `field` to `key` after its length, so that the fields can't run into each other.
+fn push_key_field(key: &mut Vec<u8>, field: &[u8]) {
+    key.extend((field.len() as u64).to_le_bytes());
+    key.extend_from_slice(field);
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Reads the cached projects, if the cache at `path` exists, has the given `key` and none of the
+/// scanned directories have been modified since it was written.
+///
+/// The cache starts with a header line and the key, followed by a `<mtime> <path>` line for each
+/// scanned directory, a blank line and a `<source directory>\0<path>` line for each project,
+/// which neither path can contain. It ends with the number of projects, so that a cache cut
+/// short while it was written isn't read.
+pub fn read_cache(path: &Path, key: u64) -> Option<Vec<Project>> {
+    let contents = fs::read(path).ok()?;
+    let contents = contents.strip_suffix(b"\n")?;
+    let count_start = contents.iter().rposition(|b| *b == b'\n')?;
+    let count = std::str::from_utf8(&contents[count_start + 1..])
+        .ok()?
+        .parse::<usize>()
+        .ok()?;
+    let mut lines = contents[..count_start].split(|b| *b == b'\n');
+
+    if lines.next()? != CACHE_HEADER || lines.next()? != key.to_string().as_bytes() {
+        return None;
+    }
+
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+
+        let separator = line.iter().position(|b| *b == b' ')?;
+        let (mtime, dir) = (&line[..separator], &line[separator + 1..]);
+        let modified = fs::metadata(path_from_bytes(dir)).ok()?.modified().ok()?;
+        if format_mtime(modified).as_bytes() != mtime {
+            return None;
+        }
+    }
+
+    let projects = lines
+        .map(|line| {
+            let separator = line.iter().position(|b| *b == b'\0')?;
+            let (src_dir, project) = (&line[..separator], &line[separator + 1..]);
+            let project = path_from_bytes(project);
+            Some(if src_dir.is_empty() {
+                Project::new(project)
+            } else {
+                Project::with_src_dir(project, path_from_bytes(src_dir))
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    (projects.len() == count).then_some(projects)
+}
+
+/// Writes `scan` to the cache at `path` with the given `key`.
+pub fn write_cache(path: &Path, key: u64, scan: &Scan) -> Result<()> {
+    let mut contents = Vec::new();
+    contents.extend_from_slice(CACHE_HEADER);
+    contents.push(b'\n');
+    contents.extend_from_slice(key.to_string().as_bytes());
+    contents.push(b'\n');
+
+    for dir in &scan.dirs {
+        let modified = fs::metadata(dir)?.modified()?;
+        contents.extend_from_slice(format_mtime(modified).as_bytes());
+        contents.push(b' ');
+        contents.extend_from_slice(dir.as_os_str().as_encoded_bytes());
+        contents.push(b'\n');
+    }
+
+    contents.push(b'\n');
+    for project in &scan.projects {
+        if let Some(src_dir) = project.src_dir() {
+            contents.extend_from_slice(src_dir.as_os_str().as_encoded_bytes());
+        }
+        contents.push(b'\0');
+        contents.extend_from_slice(project.full_path().as_os_str().as_encoded_bytes());
+        contents.push(b'\n');
+    }
+    contents.extend_from_slice(scan.projects.len().to_string().as_bytes());
+    contents.push(b'\n');
+
+    write_cache_file(path, &contents)
+}
+
+fn format_mtime(mtime: SystemTime) -> String {
+    let since_epoch = mtime.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!(
+        "{}.{:09}",
+        since_epoch.as_secs(),
+        since_epoch.subsec_nanos()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Returns a scan of the source directory `src` finding the `projects` in it.
+    fn scan_of(src: &Path, projects: &[&str]) -> Scan {
+        Scan {
+            projects: projects
+                .iter()
+                .map(|name| Project::with_src_dir(src.join(name), src.to_path_buf()))
+                .collect(),
+            dirs: vec![src.to_path_buf()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn written_projects_are_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("find_project").join("projects");
+        let src = dir.path().join("src");
+        fs::create_dir(&src).unwrap();
+
+        let mut scan = scan_of(&src, &["alpha", "b c"]);
+        scan.projects.push(Project::new(dir.path().join("listed")));
+        write_cache(&path, 1, &scan).unwrap();
+        assert_eq!(read_cache(&path, 1), Some(scan.projects));
+
+        write_cache(&path, 1, &scan_of(&src, &[])).unwrap();
+        assert_eq!(read_cache(&path, 1), Some(Vec::new()));
+    }
+
+    #[test]
+    fn changed_key_invalidates_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("projects");
+        write_cache(&path, 1, &Scan::default()).unwrap();
+        assert_eq!(read_cache(&path, 2), None);
+
+        let src_dirs = [SrcDir {
+            path: PathBuf::from("/home/user/src"),
+            min_depth: 1,
+            max_depth: 1,
+            ..Default::default()
+        }];
+        let opts = ScanOptions::default();
+        let key = cache_key(&src_dirs, &opts);
+        // Kept the same between Rust releases, so that upgrading doesn't throw away the cache
+        assert_eq!(key, 11004767279559839788);
+
+        let changed = |change: fn(&mut SrcDir)| {
+            let mut src_dirs = src_dirs.clone();
+            change(&mut src_dirs[0]);
+            cache_key(&src_dirs, &opts)
+        };
+        assert_ne!(changed(|src_dir| src_dir.max_depth = 2), key);
+        assert_ne!(
+            changed(|src_dir| src_dir.markers = vec!["Cargo.toml".into()]),
+            key
+        );
+        assert_ne!(
+            changed(|src_dir| src_dir.exclude = vec!["ab".into()]),
+            changed(|src_dir| src_dir.exclude = vec!["a".into(), "b".into()])
+        );
+        // Settings of new sessions don't change which projects are found
+        assert_eq!(
+            changed(|src_dir| src_dir.startup = Some("nvim".into())),
+            key
+        );
+
+        let hidden = ScanOptions {
+            hidden: true,
+            ..opts
+        };
+        assert_ne!(cache_key(&src_dirs, &hidden), key);
+        assert_ne!(cache_key(&[], &opts), key);
+    }
+
+    #[test]
+    fn modified_dir_invalidates_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("projects");
+        let src = dir.path().join("src");
+        fs::create_dir(&src).unwrap();
+
+        write_cache(&path, 1, &scan_of(&src, &["alpha"])).unwrap();
+        assert!(read_cache(&path, 1).is_some());
+
+        // Directories can't be opened as files on Windows
+        #[cfg(unix)]
+        {
+            fs::File::open(&src)
+                .unwrap()
+                .set_modified(UNIX_EPOCH + Duration::from_secs(1))
+                .unwrap();
+            assert_eq!(read_cache(&path, 1), None);
+            write_cache(&path, 1, &scan_of(&src, &["alpha"])).unwrap();
+        }
+
+        fs::remove_dir(&src).unwrap();
+        assert_eq!(read_cache(&path, 1), None);
+    }
+
+    #[test]
+    fn truncated_or_corrupt_cache_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("projects");
+        let src = dir.path().join("src");
+        fs::create_dir(&src).unwrap();
+
+        write_cache(&path, 1, &scan_of(&src, &["alpha", "beta"])).unwrap();
+        let contents = fs::read(&path).unwrap();
+        for len in 0..contents.len() {
+            fs::write(&path, &contents[..len]).unwrap();
+            assert_eq!(
+                read_cache(&path, 1),
+                None,
+                "{:?}",
+                String::from_utf8_lossy(&contents[..len])
+            );
+        }
+
+        let without_separators = contents
+            .iter()
+            .map(|b| if *b == b'\0' { b' ' } else { *b })
+            .collect::<Vec<_>>();
+        let older_format = [
+            &b"find_project cache v2"[..],
+            &contents[CACHE_HEADER.len()..],
+        ]
+        .concat();
+        for corrupt in [&b"not a cache\n"[..], &without_separators, &older_format] {
+            fs::write(&path, corrupt).unwrap();
+            assert_eq!(read_cache(&path, 1), None);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Generating shell completion scripts from the command line interface.
+//!
+//! The flags and subcommands are completed by the scripts `clap_complete` generates. Project
+//! names are completed on top of them by listing the projects in the project cache with the
+//! hidden `--list-cached` flag, which prints nothing instead of scanning or creating the config.
+
+use anyhow::{anyhow, Result};
+use clap::{Command, ValueEnum};
+
+/// The shells completion scripts can be generated for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl From<Shell> for clap_complete::Shell {
+    fn from(shell: Shell) -> Self {
+        match shell {
+            Shell::Bash => clap_complete::Shell::Bash,
+            Shell::Zsh => clap_complete::Shell::Zsh,
+            Shell::Fish => clap_complete::Shell::Fish,
+        }
+    }
+}
+
+/// Returns the completion script for `shell` completing the arguments of `command`, which is run
+/// as `bin_name`.
+///
+/// Fails if the script `clap_complete` generates doesn't have the parts the project names are
+/// added to, which would otherwise silently stop completing them.
+pub fn generate(shell: Shell, command: &mut Command, bin_name: &str) -> Result<String> {
+    let mut script = Vec::new();
+    clap_complete::generate(
+        clap_complete::Shell::from(shell),
+        command,
+        bin_name,
+        &mut script,
+    );
+    let script = String::from_utf8_lossy(&script);
+
+    match shell {
+        Shell::Bash => bash(&script, command, bin_name),
+        Shell::Zsh => zsh(&script, command, bin_name),
+        Shell::Fish => Ok(fish(&script, command, bin_name)),
+    }
+}
+
+/// The shell command printing the name of each project, one per line.
+fn list_project_names(bin_name: &str) -> String {
+    format!(
+        "{} --list --list-cached --format tsv 2>/dev/null | cut -f1",
+        bin_name
+    )
+}
+
+/// Returns the names of the subcommands of `command`, including `help`.
+fn subcommands(command: &Command) -> Vec<&str> {
+    command.get_subcommands().map(Command::get_name).collect()
+}
+
+/// Returns the flags of `command` that are followed by a value, like `--sort` and `-q`.
+fn value_flags(command: &Command) -> Vec<String> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && arg.get_action().takes_values())
+        .flat_map(|arg| {
+            let longs = arg.get_long_and_visible_aliases().into_iter().flatten();
+            longs
+                .map(|long| format!("--{}", long))
+                .chain(arg.get_short().map(|short| format!("-{}", short)))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Wraps the generated completion function, adding the project names to the completions of
+/// words that aren't flags, flag values or after a subcommand.
+///
+/// The names are read a line at a time and quoted, so that names with spaces stay whole.
+fn bash(script: &str, command: &Command, bin_name: &str) -> Result<String> {
+    // The name `clap_complete` gives the function
+    let function = format!("_{}", bin_name.replace('-', "__"));
+    if !script
+        .lines()
+        .any(|line| line == format!("{}() {{", function))
+    {
+        return Err(anyhow!(
+            "The bash script of clap_complete no longer defines `{}`.",
+            function
+        ));
+    }
+    let case = |patterns: Vec<String>, indent: &str| {
+        if patterns.is_empty() {
+            return String::new();
+        }
+        format!(
+            "{indent}case \"$word\" in\n{indent}{}) return 0 ;;\n{indent}esac\n",
+            patterns.join("|")
+        )
+    };
+    let subcommands = subcommands(command).into_iter().map(String::from).collect();
+
+    Ok(format!(
+        r#"{script}
+{function}_projects() {{
+    {function} "$@"
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" word name
+    [[ $cur == -* ]] && return 0
+    for word in "${{COMP_WORDS[@]:1:COMP_CWORD-1}}"; do
+{subcommand_case}    done
+    word="${{COMP_WORDS[COMP_CWORD-1]}}"
+{value_flag_case}    while IFS= read -r name; do
+        [[ $name == "$cur"* ]] && COMPREPLY+=("$(printf '%q' "$name")")
+    done < <({list})
+}}
+
+complete -F {function}_projects -o bashdefault -o default {bin_name}
+"#,
+        subcommand_case = case(subcommands, "        "),
+        value_flag_case = case(value_flags(command), "    "),
+        list = list_project_names(bin_name),
+    ))
+}
+
+/// Completes the project names for the positional argument of `command`, which the generated
+/// script completes as files, with a function defined before the script dispatches to it.
+fn zsh(script: &str, command: &Command, bin_name: &str) -> Result<String> {
+    let Some(positional) = command.get_positionals().next() else {
+        return Ok(script.to_string());
+    };
+    let function = format!("_{}_projects", bin_name);
+    let argument = format!("'::{}", positional.get_id());
+    let dispatch = format!("if [ \"$funcstack[1]\" = \"_{}\" ]; then", bin_name);
+
+    let mut completed = String::with_capacity(script.len());
+    let (mut defined, mut replaced) = (false, false);
+    for line in script.split_inclusive('\n') {
+        if !defined && line.starts_with(&dispatch) {
+            completed.push_str(&format!(
+                "(( $+functions[{function}] )) ||\n{function}() {{\n    \
+                local -a projects\n    \
+                projects=(${{(f)\"$({list})\"}})\n    \
+                compadd -a projects\n}}\n\n",
+                list = list_project_names(bin_name),
+            ));
+            defined = true;
+        }
+        if !replaced && line.starts_with(&argument) && line.contains(":_default'") {
+            completed.push_str(&line.replacen(":_default'", &format!(":{}'", function), 1));
+            replaced = true;
+        } else {
+            completed.push_str(line);
+        }
+    }
+
+    if !(defined && replaced) {
+        return Err(anyhow!(
+            "The zsh script of clap_complete no longer completes `{}` where expected.",
+            positional.get_id()
+        ));
+    }
+
+    Ok(completed)
+}
+
+/// Escapes `text` to be used in a single quoted fish string.
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// Adds the project names to the completions of words that aren't after a subcommand.
+fn fish(script: &str, command: &Command, bin_name: &str) -> String {
+    format!(
+        "{}complete -c {} -n 'not __fish_seen_subcommand_from {}' -f -a '({})'\n",
+        script,
+        bin_name,
+        subcommands(command).join(" "),
+        fish_escape(&list_project_names(bin_name))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Arg;
+
+    use super::*;
+
+    fn command() -> Command {
+        Command::new("fp")
+            .arg(Arg::new("name").help("The project to switch to"))
+            .arg(
+                Arg::new("sort")
+                    .long("sort")
+                    .help("How to order the projects")
+                    .value_parser(["mtime", "name"]),
+            )
+            .arg(
+                Arg::new("multi")
+                    .long("multi")
+                    .short('m')
+                    .help("Select several projects")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .subcommand(Command::new("clean").about("Kill the sessions of deleted projects"))
+    }
+
+    #[test]
+    fn completes_project_names_after_generated_script() {
+        let bash = generate(Shell::Bash, &mut command(), "fp").unwrap();
+        assert!(bash.contains("_fp() {"));
+        assert!(bash.contains("    _fp \"$@\"\n"));
+        assert!(bash.contains("    clean|help) return 0 ;;\n"));
+        assert!(bash.contains("    --sort) return 0 ;;\n"));
+        assert!(bash.ends_with("complete -F _fp_projects -o bashdefault -o default fp\n"));
+
+        let zsh = generate(Shell::Zsh, &mut command(), "fp").unwrap();
+        assert!(zsh.contains("'::name -- The project to switch to:_fp_projects' \\\n"));
+        let function = zsh.find("_fp_projects() {").unwrap();
+        assert!(function < zsh.find("if [ \"$funcstack[1]\" = \"_fp\" ]").unwrap());
+
+        let fish = generate(Shell::Fish, &mut command(), "fp").unwrap();
+        assert!(fish.contains("-l sort"));
+        assert!(fish.ends_with(
+            "complete -c fp -n 'not __fish_seen_subcommand_from clean help' -f \
+            -a '(fp --list --list-cached --format tsv 2>/dev/null | cut -f1)'\n"
+        ));
+    }
+
+    #[test]
+    fn fails_when_generated_script_changes() {
+        let zsh = generate(Shell::Zsh, &mut command(), "fp").unwrap();
+        assert!(super::zsh(&zsh.replace("$funcstack[1]", "$0"), &command(), "fp").is_err());
+        assert!(super::zsh("'::name -- The project:_files' \\\n", &command(), "fp").is_err());
+        assert!(bash("_other() {\n}\n", &command(), "fp").is_err());
+    }
+}
@@ -0,0 +1,1730 @@
+//! Reading the config file that lists the source directories to search for projects.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::multiplexer::{Layout, MultiplexerKind, SessionNameTemplate, TmuxServer};
+use crate::scan::SortOrder;
+use crate::PROJECT_NAME;
+
+/// The source directory used when the config doesn't list any, relative to `$HOME`.
+pub const DEFAULT_SRC_DIR: &str = "src";
+pub const DEFAULT_SRC_DIR_DEPTH: u8 = 2;
+/// The environment variable replacing [`DEFAULT_SRC_DIR`], like `code 3` for `~/code` searched
+/// three directories deep. Setting it to an empty value stops any directory from being used.
+pub const DEFAULT_SRC_DIR_ENV_VAR: &str = "FIND_PROJECT_DEFAULT_SRC_DIR";
+/// The depth of source directories listed or added without one.
+pub const DEFAULT_DEPTH: u8 = 1;
+
+/// A directory holding projects.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SrcDir {
+    /// The *full* path to the directory containing projects.
+    pub path: PathBuf,
+    /// The least number of directories between the `path` to the actual projects
+    pub min_depth: u8,
+    /// The greatest number of directories between the `path` to the actual projects
+    pub max_depth: u8,
+    /// The files or directories that mark a directory as a project, if any are required.
+    pub markers: Vec<String>,
+    /// The names of the directories to neither search nor list as projects.
+    pub exclude: Vec<String>,
+    /// The patterns of the paths, relative to `path`, of the only directories searched for
+    /// projects, one component per level, where an empty list searches every directory.
+    pub search_only: Vec<String>,
+    /// The command run in new sessions for the projects in this directory.
+    pub startup: Option<String>,
+    /// The name of the layout applied to new sessions for the projects in this directory.
+    pub layout: Option<String>,
+    /// How the sessions for the projects in this directory are named.
+    pub session_name: Option<SessionNameTemplate>,
+}
+
+/// The settings read from a config file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    pub src_dirs: Vec<SrcDir>,
+    /// The *full* paths to projects listed without scanning for them.
+    pub projects: Vec<PathBuf>,
+    /// Whether to skip using `~/src` when no source directories are configured.
+    pub no_default: bool,
+    /// The path to the `fzf` binary.
+    pub fzf: Option<PathBuf>,
+    /// The path to the `tmux` binary.
+    pub tmux: Option<PathBuf>,
+    /// The tmux server to use instead of the default one.
+    pub tmux_server: Option<TmuxServer>,
+    /// How many times switching to a tmux session is retried if it fails right after starting.
+    pub tmux_retries: Option<u32>,
+    /// The path to the `zellij` binary.
+    pub zellij: Option<PathBuf>,
+    /// The multiplexer to open sessions in, detected from the environment if unset.
+    pub multiplexer: Option<MultiplexerKind>,
+    /// How sessions are named, unless their source directory sets its own template.
+    pub session_name: Option<SessionNameTemplate>,
+    /// Replaces the characters tmux doesn't allow in session names.
+    pub session_name_replacement: Option<char>,
+    /// The markers used by source directories that don't set their own.
+    pub markers: Vec<String>,
+    /// The directories searched in source directories that don't set their own.
+    pub search_only: Vec<String>,
+    /// Whether to search hidden directories.
+    pub hidden: bool,
+    /// Whether to search symlinked directories.
+    pub follow_symlinks: bool,
+    /// Whether git repositories are projects whose contents aren't searched.
+    pub git_roots: bool,
+    /// The directory names excluded from every source directory.
+    pub exclude: Vec<String>,
+    /// Whether excluded names are matched ignoring case.
+    pub exclude_ignore_case: bool,
+    /// How long scanning can take before it's stopped, where zero means no limit.
+    pub scan_timeout: Option<Duration>,
+    /// How many directories can be read while scanning before it's stopped, where zero means no
+    /// limit.
+    pub max_dirs: Option<usize>,
+    /// How many directories a source directory is estimated to have before a warning that
+    /// scanning it may take long is printed, where zero disables the warning.
+    pub scan_warning: Option<u64>,
+    /// The order projects are listed in.
+    pub sort: Option<SortOrder>,
+    /// Whether to hide the fzf preview of the highlighted project.
+    pub no_preview: bool,
+    /// Whether to show the source directory of each project after its path in fzf.
+    pub show_source: bool,
+    /// The prompt shown by fzf instead of its default.
+    pub prompt: Option<String>,
+    /// The header shown by fzf above the projects, where `{count}` is the number of projects.
+    pub header: Option<String>,
+    /// Shown in fzf before the projects that have an active session, where an empty marker
+    /// shows none.
+    pub active_marker: Option<String>,
+    /// Whether to ring the terminal bell when a new session is created.
+    pub bell: bool,
+    /// Whether to open a shell in the selected project instead of a multiplexer session.
+    pub cd: bool,
+    /// Whether to list the projects zoxide knows about first, ranked by its scores.
+    pub zoxide: bool,
+    /// The commands run in new sessions for projects containing a marker.
+    pub startup: Vec<MarkerStartup>,
+    /// The layouts that can be applied to new sessions by name.
+    pub layouts: HashMap<String, Layout>,
+    /// The name of the layout applied to new sessions by default.
+    pub layout: Option<String>,
+    /// The name of the window selected when switching to a session that has one.
+    pub window: Option<String>,
+}
+
+/// A command run in new sessions for projects containing `marker`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarkerStartup {
+    pub marker: String,
+    pub command: String,
+}
+
+/// Returns `$XDG_CONFIG_HOME`, falling back to `~/.config` when it's unset, empty or not an
+/// absolute path as per the XDG Base Directory Specification.
+pub fn config_home() -> Result<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME").map(PathBuf::from) {
+        Some(path) if path.is_absolute() => Ok(path),
+        _ => Ok(home_dir()?.join(".config")),
+    }
+}
+
+/// Returns the config file to read from `config_dir`, preferring `find_project.toml` over
+/// `find_project.conf`.
+pub fn find_config_file(config_dir: &Path) -> PathBuf {
+    let toml_path = config_dir.join(format!("{}.toml", PROJECT_NAME));
+    let conf_path = config_dir.join(format!("{}.conf", PROJECT_NAME));
+
+    if !toml_path.exists() {
+        return conf_path;
+    }
+
+    if conf_path.exists() {
+        eprintln!(
+            "Found both '{}' and '{}', ignoring the latter.",
+            toml_path.display(),
+            conf_path.display()
+        );
+    }
+
+    toml_path
+}
+
+/// The environment variable that sets the user's config file when `--config` isn't passed.
+pub const CONFIG_ENV_VAR: &str = "FIND_PROJECT_CONFIG";
+
+/// Returns the user's config file given with `--config` as `flag`, or else with
+/// [`CONFIG_ENV_VAR`] set to `env_path`, along with how it was given to name it in messages.
+///
+/// Returns `None` if neither is set, to use the config file in the config directory.
+pub fn config_override(
+    flag: Option<&Path>,
+    env_path: Option<OsString>,
+) -> Option<(PathBuf, &'static str)> {
+    match (flag, env_path.filter(|path| !path.is_empty())) {
+        (Some(path), _) => Some((path.to_path_buf(), "--config")),
+        (None, Some(path)) => Some((PathBuf::from(path), "$FIND_PROJECT_CONFIG")),
+        (None, None) => None,
+    }
+}
+
+/// Returns the config file at `path`, given on the command line or in the environment, made
+/// absolute against `cwd` and with any symlinks resolved, so that messages and paths relative to
+/// it don't depend on how it was given.
+///
+/// A `path` that doesn't exist is only made absolute, since the config can be created there.
+pub fn resolve_config_path(path: &Path, cwd: &Path) -> PathBuf {
+    let path = cwd.join(path);
+
+    fs::canonicalize(&path).unwrap_or(path)
+}
+
+/// The comments at the top of the config written on the first run.
+const CONFIG_COMMENTS: &str = "\
+# Each line is a directory holding projects followed by the number of directories between it and
+# the projects, e.g. `~/src 2` finds projects like `~/src/github.com/project`.
+#
+# Lines starting with `#` are comments. A line containing just `lenient` skips malformed lines
+# instead of failing, and one containing just `no-default` stops `~/src` from being used when no
+# directories are listed.
+";
+
+/// The source directory listed in the config written on the first run.
+const DEFAULT_CONFIG_SRC_DIR: &str = "~/src 2\n";
+
+/// Writes the default config to `path`, creating its parent directories.
+///
+/// The config doesn't list `~/src` when [`DEFAULT_SRC_DIR_ENV_VAR`] is set, so that the directory
+/// it sets is used instead.
+pub fn create_default_config(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let src_dir = match env::var_os(DEFAULT_SRC_DIR_ENV_VAR) {
+        Some(_) => "",
+        None => DEFAULT_CONFIG_SRC_DIR,
+    };
+    fs::write(path, format!("{}{}", CONFIG_COMMENTS, src_dir))?;
+
+    Ok(())
+}
+
+/// Writes a config without any source directories to `path`, creating its parent directories,
+/// for them to be added with [`add_src_dir`].
+pub fn create_commented_config(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, CONFIG_COMMENTS)?;
+
+    Ok(())
+}
+
+/// Appends a source directory at `path`, searched at `depth`, to the config file at
+/// `config_path`, creating the file if it doesn't exist, and returns it.
+///
+/// `path` is expanded like the paths in the config and made absolute. Fails if it isn't a
+/// directory or the config already lists it.
+pub fn add_src_dir(config_path: &Path, path: &str, depth: u8, home: &Path) -> Result<SrcDir> {
+    let src_dir = SrcDir {
+        path: std::path::absolute(expand_path(path, home)?)?,
+        min_depth: depth,
+        max_depth: depth,
+        ..Default::default()
+    };
+    check_src_dir(&src_dir)?;
+
+    let Some(path) = src_dir.path.to_str() else {
+        return Err(anyhow!(
+            "Source directory '{}' can't be written to the config, it isn't UTF-8.",
+            src_dir.path.display()
+        ));
+    };
+    // Paths are expanded when the config is read, and there's no way to escape a `$`
+    if path.contains('$') {
+        return Err(anyhow!(
+            "Source directory '{}' can't be written to the config, it contains a `$`.",
+            path
+        ));
+    }
+
+    let mut contents = match fs::read_to_string(config_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let config = parse_config_contents(config_path, &contents, home)?;
+    if config
+        .src_dirs
+        .iter()
+        .any(|listed| listed.path == src_dir.path)
+    {
+        return Err(anyhow!(
+            "Source directory '{}' is already listed in '{}'.",
+            path,
+            config_path.display()
+        ));
+    }
+
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    if config_path.extension() == Some(OsStr::new("toml")) {
+        contents.push_str(&format!(
+            "\n[[source]]\npath = {}\ndepth = {}\n",
+            toml::Value::String(path.to_string()),
+            depth
+        ));
+    } else if path.contains(char::is_whitespace) || path.starts_with('"') {
+        let escaped = path.replace('\\', "\\\\").replace('"', "\\\"");
+        contents.push_str(&format!("\"{}\" {}\n", escaped, depth));
+    } else {
+        contents.push_str(&format!("{} {}\n", path, depth));
+    }
+
+    if let Some(dir) = config_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(config_path, contents)?;
+
+    Ok(src_dir)
+}
+
+/// Checks that the path of `src_dir` is a directory, without searching it.
+pub fn check_src_dir(src_dir: &SrcDir) -> Result<()> {
+    check_dir("Source directory", &src_dir.path)
+}
+
+/// Checks that the explicitly listed project at `path` is a directory.
+pub fn check_project(path: &Path) -> Result<()> {
+    check_dir("Project", path)
+}
+
+/// Checks that `path` is a directory, naming it `what` in the errors.
+fn check_dir(what: &str, path: &Path) -> Result<()> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => Ok(()),
+        Ok(_) => Err(anyhow!("{} '{}' is not a directory.", what, path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(anyhow!("{} '{}' does not exist.", what, path.display()))
+        }
+        Err(e) => Err(anyhow!(
+            "Failed to read {} '{}': {}.",
+            what.to_lowercase(),
+            path.display(),
+            e
+        )),
+    }
+}
+
+/// Reads the config file at `path`, which is parsed as TOML if it has a `.toml` extension and as
+/// the line format otherwise.
+///
+/// A leading `~` in paths is expanded to `home`, and source directories with a `*` or `?` in
+/// their path are replaced by the directories it matches. If the config doesn't list any source
+/// directories, `~/src` is used unless the config disables it with `no-default`.
+pub fn read_config_file<P: AsRef<Path>>(path: P, home: &Path) -> Result<Config> {
+    let mut config = with_default_src_dir(parse_config_file(path.as_ref(), home)?, home)?;
+    // Expanded last, so that `~/src` isn't used in place of a glob that doesn't match anything
+    config.src_dirs = expand_globs(config.src_dirs);
+
+    Ok(config)
+}
+
+/// Reads each of the config files at `paths` that exists, in order, layering each one on top of
+/// the ones before it.
+///
+/// The source directories of every file are used, except that one listed again by a later file
+/// is replaced by it, and the settings of later files take precedence. `~/src` is only used if
+/// none of the files list a source directory, as with [`read_config_file`].
+pub fn read_config_files(paths: &[PathBuf], home: &Path) -> Result<Config> {
+    let mut config = Config::default();
+    for path in paths.iter().filter(|path| path.is_file()) {
+        let layer = parse_config_file(path, home)
+            .map_err(|e| anyhow!("Failed to read '{}': {}", path.display(), e))?;
+        config = config.layered_with(layer);
+    }
+
+    let mut config = with_default_src_dir(config, home)?;
+    // Expanded last, so that `~/src` isn't used in place of a glob that doesn't match anything
+    config.src_dirs = expand_globs(config.src_dirs);
+
+    Ok(config)
+}
+
+/// Reads a line format config from `reader`, such as stdin, as described in [`read_config_file`].
+///
+/// Unlike a config file, `~/src` isn't used when no source directories are listed.
+pub fn read_line_config<R: Read>(mut reader: R, home: &Path) -> Result<Config> {
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+
+    let mut config = parse_line_config(&contents, home)?;
+    config.src_dirs = expand_globs(config.src_dirs);
+
+    Ok(config)
+}
+
+/// Returns the directory of the config shared by every user, if the platform has one.
+#[cfg(unix)]
+pub fn system_config_dir() -> Option<PathBuf> {
+    Some(Path::new("/etc").join(PROJECT_NAME))
+}
+
+#[cfg(not(unix))]
+pub fn system_config_dir() -> Option<PathBuf> {
+    None
+}
+
+impl Config {
+    /// Returns this config with the settings of `other` taking precedence.
+    fn layered_with(self, other: Config) -> Config {
+        let mut src_dirs = self
+            .src_dirs
+            .into_iter()
+            .filter(|src_dir| {
+                !other
+                    .src_dirs
+                    .iter()
+                    .any(|other| other.path == src_dir.path)
+            })
+            .collect::<Vec<_>>();
+        src_dirs.extend(other.src_dirs);
+        let mut projects = self.projects;
+        projects.extend(
+            other
+                .projects
+                .into_iter()
+                .filter(|path| !projects.contains(path))
+                .collect::<Vec<_>>(),
+        );
+        let mut layouts = self.layouts;
+        layouts.extend(other.layouts);
+
+        Config {
+            src_dirs,
+            projects,
+            no_default: self.no_default || other.no_default,
+            fzf: other.fzf.or(self.fzf),
+            tmux: other.tmux.or(self.tmux),
+            tmux_server: other.tmux_server.or(self.tmux_server),
+            tmux_retries: other.tmux_retries.or(self.tmux_retries),
+            zellij: other.zellij.or(self.zellij),
+            multiplexer: other.multiplexer.or(self.multiplexer),
+            session_name: other.session_name.or(self.session_name),
+            session_name_replacement: other
+                .session_name_replacement
+                .or(self.session_name_replacement),
+            markers: [self.markers, other.markers].concat(),
+            search_only: [self.search_only, other.search_only].concat(),
+            hidden: self.hidden || other.hidden,
+            follow_symlinks: self.follow_symlinks || other.follow_symlinks,
+            git_roots: self.git_roots || other.git_roots,
+            exclude: [self.exclude, other.exclude].concat(),
+            exclude_ignore_case: self.exclude_ignore_case || other.exclude_ignore_case,
+            scan_timeout: other.scan_timeout.or(self.scan_timeout),
+            max_dirs: other.max_dirs.or(self.max_dirs),
+            scan_warning: other.scan_warning.or(self.scan_warning),
+            sort: other.sort.or(self.sort),
+            no_preview: self.no_preview || other.no_preview,
+            show_source: self.show_source || other.show_source,
+            prompt: other.prompt.or(self.prompt),
+            header: other.header.or(self.header),
+            active_marker: other.active_marker.or(self.active_marker),
+            bell: self.bell || other.bell,
+            cd: self.cd || other.cd,
+            zoxide: self.zoxide || other.zoxide,
+            // The first matching marker is used, so the later ones go first
+            startup: [other.startup, self.startup].concat(),
+            layouts,
+            layout: other.layout.or(self.layout),
+            window: other.window.or(self.window),
+        }
+    }
+}
+
+/// Parses the config file at `path` as described in [`read_config_file`], without adding the
+/// default source directory.
+fn parse_config_file(path: &Path, home: &Path) -> Result<Config> {
+    parse_config_contents(path, &fs::read_to_string(path)?, home)
+}
+
+/// Parses the `contents` of the config file at `path`, in the format its extension names.
+fn parse_config_contents(path: &Path, contents: &str, home: &Path) -> Result<Config> {
+    // Editors on Windows may start the file with a byte order mark and end lines with `\r\n`,
+    // the latter of which is stripped along with the rest of the whitespace around each line
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+
+    if path.extension() == Some(OsStr::new("toml")) {
+        parse_toml_config(contents, home)
+    } else {
+        parse_line_config(contents, home)
+    }
+}
+
+/// Replaces each source directory with a `*` or `?` in its path with a source directory for each
+/// directory the path matches, reporting the ones that match nothing on stderr.
+fn expand_globs(src_dirs: Vec<SrcDir>) -> Vec<SrcDir> {
+    let mut expanded = Vec::with_capacity(src_dirs.len());
+
+    for src_dir in src_dirs {
+        if !src_dir
+            .path
+            .components()
+            .any(|component| is_glob(component.as_os_str()))
+        {
+            expanded.push(src_dir);
+            continue;
+        }
+
+        let paths = glob_dirs(&src_dir.path);
+        if paths.is_empty() {
+            eprintln!(
+                "Source directory '{}' does not match any directory. Skipping it.",
+                src_dir.path.display()
+            );
+        }
+        expanded.extend(paths.into_iter().map(|path| SrcDir {
+            path,
+            ..src_dir.clone()
+        }));
+    }
+
+    expanded
+}
+
+/// Returns whether the path component `name` is a glob pattern.
+fn is_glob(name: &OsStr) -> bool {
+    name.to_str().is_some_and(|name| name.contains(['*', '?']))
+}
+
+/// Returns the directories matching `pattern`, a path whose components may contain `*` to match
+/// any characters and `?` to match a single character, in the order of their names.
+fn glob_dirs(pattern: &Path) -> Vec<PathBuf> {
+    let mut matches = vec![PathBuf::new()];
+
+    for component in pattern.components() {
+        let name = component.as_os_str();
+        let Some(name) = name.to_str().filter(|_| is_glob(name)) else {
+            for path in &mut matches {
+                path.push(component);
+            }
+            continue;
+        };
+
+        let pattern = name.chars().collect::<Vec<_>>();
+        matches = matches
+            .iter()
+            .flat_map(|dir| {
+                let mut children = fs::read_dir(dir)
+                    .into_iter()
+                    .flatten()
+                    .map_while(Result::ok)
+                    .filter(|entry| {
+                        let name = entry.file_name();
+                        name.to_str().is_some_and(|name| {
+                            glob_matches(&pattern, &name.chars().collect::<Vec<_>>())
+                        })
+                    })
+                    .map(|entry| entry.path())
+                    // Unlike `entry.file_type()`, this follows symlinks
+                    .filter(|path| path.is_dir())
+                    .collect::<Vec<_>>();
+                children.sort();
+                children
+            })
+            .collect();
+    }
+
+    matches
+}
+
+/// Returns whether `name` matches the glob `pattern`, where wildcards don't match a leading `.`
+/// so that hidden directories are only matched explicitly, like in a shell.
+pub fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    if name.first() == Some(&'.') && pattern.first() != Some(&'.') {
+        return false;
+    }
+
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.first(), name.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+            }
+            (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+            (Some(p), Some(n)) if p == n => matches(&pattern[1..], &name[1..]),
+            _ => false,
+        }
+    }
+
+    matches(pattern, name)
+}
+
+/// Adds `~/src` to `config` if it doesn't list any source directories and doesn't disable it
+/// with `no-default`, or the directory set with [`DEFAULT_SRC_DIR_ENV_VAR`] instead.
+fn with_default_src_dir(mut config: Config, home: &Path) -> Result<Config> {
+    if !config.src_dirs.is_empty() || config.no_default {
+        return Ok(config);
+    }
+
+    let env_value = env::var_os(DEFAULT_SRC_DIR_ENV_VAR);
+    let default = default_src_dir(env_value.as_deref(), home)
+        .map_err(|e| anyhow!("${}: {}", DEFAULT_SRC_DIR_ENV_VAR, e))?;
+    if let Some(src_dir) = default {
+        config.src_dirs.push(SrcDir {
+            markers: config.markers.clone(),
+            exclude: config.exclude.clone(),
+            search_only: config.search_only.clone(),
+            ..src_dir
+        });
+    }
+
+    Ok(config)
+}
+
+/// Returns the source directory used when the config doesn't list any, given the value of
+/// [`DEFAULT_SRC_DIR_ENV_VAR`] as `env_value`, or `None` if it's set to an empty value.
+///
+/// The value is a path relative to `home` optionally followed by a depth, which defaults to
+/// [`DEFAULT_SRC_DIR_DEPTH`]. `~/src` is used if it's unset.
+pub fn default_src_dir(env_value: Option<&OsStr>, home: &Path) -> Result<Option<SrcDir>> {
+    let Some(value) = env_value else {
+        return Ok(Some(SrcDir {
+            path: home.join(DEFAULT_SRC_DIR),
+            min_depth: DEFAULT_SRC_DIR_DEPTH,
+            max_depth: DEFAULT_SRC_DIR_DEPTH,
+            ..Default::default()
+        }));
+    };
+    let Some(value) = value.to_str().map(str::trim) else {
+        return Err(anyhow!("'{}' isn't UTF-8.", value.to_string_lossy()));
+    };
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    let (path, depth) = split_path(value)?;
+    let depth = depth.trim();
+    let (min_depth, max_depth) = if depth.is_empty() {
+        (DEFAULT_SRC_DIR_DEPTH, DEFAULT_SRC_DIR_DEPTH)
+    } else {
+        parse_depth(depth)?
+    };
+
+    Ok(Some(SrcDir {
+        // Joining an absolute path replaces `home`
+        path: home.join(expand_path(&path, home)?),
+        min_depth,
+        max_depth,
+        ..Default::default()
+    }))
+}
+
+/// The contents of a `find_project.toml` config file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct TomlConfig {
+    #[serde(default)]
+    no_default: bool,
+    /// The depth of the `[[source]]` tables without one, defaulting to [`DEFAULT_DEPTH`].
+    default_depth: Option<TomlDepth>,
+    fzf: Option<String>,
+    tmux: Option<String>,
+    tmux_socket_name: Option<String>,
+    tmux_socket: Option<String>,
+    tmux_retries: Option<u32>,
+    zellij: Option<String>,
+    multiplexer: Option<MultiplexerKind>,
+    session_name: Option<SessionNameTemplate>,
+    session_name_replacement: Option<char>,
+    #[serde(default)]
+    markers: Vec<String>,
+    #[serde(default)]
+    search_only: Vec<String>,
+    #[serde(default)]
+    hidden: bool,
+    #[serde(default)]
+    follow_symlinks: bool,
+    #[serde(default)]
+    git_roots: bool,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    exclude_ignore_case: bool,
+    /// In seconds.
+    scan_timeout: Option<f64>,
+    max_dirs: Option<usize>,
+    scan_warning: Option<u64>,
+    sort: Option<SortOrder>,
+    #[serde(default)]
+    no_preview: bool,
+    #[serde(default)]
+    show_source: bool,
+    prompt: Option<String>,
+    header: Option<String>,
+    active_marker: Option<String>,
+    #[serde(default)]
+    bell: bool,
+    #[serde(default)]
+    cd: bool,
+    #[serde(default)]
+    zoxide: bool,
+    #[serde(default)]
+    startup: Vec<MarkerStartup>,
+    #[serde(default)]
+    layouts: HashMap<String, Layout>,
+    layout: Option<String>,
+    window: Option<String>,
+    #[serde(default)]
+    source: Vec<TomlSource>,
+    #[serde(default)]
+    project: Vec<TomlProject>,
+}
+
+/// A `[[project]]` table in a `find_project.toml` config file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct TomlProject {
+    path: String,
+}
+
+/// A `[[source]]` table in a `find_project.toml` config file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct TomlSource {
+    path: String,
+    /// Defaults to the top level `default-depth`.
+    depth: Option<TomlDepth>,
+    markers: Option<Vec<String>>,
+    /// Overrides the top level `search-only`.
+    search_only: Option<Vec<String>>,
+    /// Excluded in addition to the top level `exclude`.
+    #[serde(default)]
+    exclude: Vec<String>,
+    startup: Option<String>,
+    /// Overrides the top level `layout`.
+    layout: Option<String>,
+    /// Overrides the top level `session-name`.
+    session_name: Option<SessionNameTemplate>,
+}
+
+/// The `depth` of a `[[source]]` table, either a number or a range like `"1-2"`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TomlDepth {
+    Exact(u8),
+    Range(String),
+}
+
+impl TomlDepth {
+    /// Returns the minimum and maximum depth.
+    fn to_range(&self) -> Result<(u8, u8)> {
+        match self {
+            TomlDepth::Exact(depth) => Ok((*depth, *depth)),
+            TomlDepth::Range(range) => parse_depth(range),
+        }
+    }
+}
+
+/// Parses a TOML config.
+fn parse_toml_config(contents: &str, home: &Path) -> Result<Config> {
+    let config = toml::from_str::<TomlConfig>(contents)?;
+
+    let unknown_layout = config
+        .source
+        .iter()
+        .filter_map(|source| source.layout.as_ref())
+        .chain(&config.layout)
+        .find(|layout| !config.layouts.contains_key(*layout));
+    if let Some(layout) = unknown_layout {
+        return Err(anyhow!("Unknown layout '{}'.", layout));
+    }
+
+    let default_depth = match &config.default_depth {
+        Some(depth) => depth
+            .to_range()
+            .map_err(|e| anyhow!("Default depth: {}", e))?,
+        None => (DEFAULT_DEPTH, DEFAULT_DEPTH),
+    };
+    let src_dirs = config
+        .source
+        .iter()
+        .map(|source| {
+            let path = expand_path(&source.path, home)
+                .map_err(|e| anyhow!("Source '{}': {}", source.path, e))?;
+            let (min_depth, max_depth) = match &source.depth {
+                Some(depth) => depth
+                    .to_range()
+                    .map_err(|e| anyhow!("Source '{}': {}", source.path, e))?,
+                None => default_depth,
+            };
+            Ok(SrcDir {
+                path,
+                min_depth,
+                max_depth,
+                markers: source
+                    .markers
+                    .clone()
+                    .unwrap_or_else(|| config.markers.clone()),
+                exclude: [config.exclude.as_slice(), source.exclude.as_slice()].concat(),
+                search_only: source
+                    .search_only
+                    .clone()
+                    .unwrap_or_else(|| config.search_only.clone()),
+                startup: source.startup.clone(),
+                layout: source.layout.clone(),
+                session_name: source.session_name.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let fzf = config
+        .fzf
+        .as_deref()
+        .map(|path| expand_path(path, home))
+        .transpose()?;
+    let tmux = config
+        .tmux
+        .as_deref()
+        .map(|path| expand_path(path, home))
+        .transpose()?;
+    let zellij = config
+        .zellij
+        .as_deref()
+        .map(|path| expand_path(path, home))
+        .transpose()?;
+    let tmux_server = match (config.tmux_socket_name, config.tmux_socket) {
+        (Some(_), Some(_)) => {
+            return Err(anyhow!(
+                "Only one of `tmux-socket-name` and `tmux-socket` can be set."
+            ))
+        }
+        (Some(name), None) => Some(TmuxServer::Name(name)),
+        (None, Some(path)) => Some(TmuxServer::Socket(expand_path(&path, home)?)),
+        (None, None) => None,
+    };
+
+    let projects = config
+        .project
+        .iter()
+        .map(|project| {
+            expand_path(&project.path, home)
+                .map_err(|e| anyhow!("Project '{}': {}", project.path, e))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Config {
+        src_dirs,
+        projects,
+        no_default: config.no_default,
+        fzf,
+        tmux,
+        tmux_server,
+        tmux_retries: config.tmux_retries,
+        zellij,
+        multiplexer: config.multiplexer,
+        session_name: config.session_name,
+        session_name_replacement: config.session_name_replacement,
+        markers: config.markers,
+        search_only: config.search_only,
+        hidden: config.hidden,
+        follow_symlinks: config.follow_symlinks,
+        git_roots: config.git_roots,
+        exclude: config.exclude,
+        exclude_ignore_case: config.exclude_ignore_case,
+        scan_timeout: config.scan_timeout.map(parse_scan_timeout).transpose()?,
+        max_dirs: config.max_dirs,
+        scan_warning: config.scan_warning,
+        sort: config.sort,
+        no_preview: config.no_preview,
+        show_source: config.show_source,
+        prompt: config.prompt,
+        header: config.header,
+        active_marker: config.active_marker,
+        bell: config.bell,
+        cd: config.cd,
+        zoxide: config.zoxide,
+        startup: config.startup,
+        layouts: config.layouts,
+        layout: config.layout,
+        window: config.window,
+    })
+}
+
+/// Parses a line format config.
+///
+/// Malformed lines are an error unless the config contains a `lenient` line, in which case they
+/// are reported on stderr and skipped.
+fn parse_line_config(contents: &str, home: &Path) -> Result<Config> {
+    let mut config = Config::default();
+    let mut errors = Vec::new();
+    let mut lenient = false;
+    let mut default_depth = None;
+    // The indices of the source directories whose depth is the default one, which can be set
+    // after them
+    let mut without_depth = Vec::new();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match line {
+            "lenient" => {
+                lenient = true;
+                continue;
+            }
+            "no-default" => {
+                config.no_default = true;
+                continue;
+            }
+            "hidden" => {
+                config.hidden = true;
+                continue;
+            }
+            "follow-symlinks" => {
+                config.follow_symlinks = true;
+                continue;
+            }
+            "git-roots" => {
+                config.git_roots = true;
+                continue;
+            }
+            "exclude-ignore-case" => {
+                config.exclude_ignore_case = true;
+                continue;
+            }
+            "no-preview" => {
+                config.no_preview = true;
+                continue;
+            }
+            "show-source" => {
+                config.show_source = true;
+                continue;
+            }
+            "bell" => {
+                config.bell = true;
+                continue;
+            }
+            "cd" => {
+                config.cd = true;
+                continue;
+            }
+            "zoxide" => {
+                config.zoxide = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        if let Some(template) = line.strip_prefix("session-name ") {
+            match SessionNameTemplate::new(template.trim()) {
+                Ok(template) => config.session_name = Some(template),
+                Err(e) => errors.push(anyhow!("Config line {} ('{}'): {}", line_number, line, e)),
+            }
+            continue;
+        }
+
+        if let Some(text) = line.strip_prefix("prompt ") {
+            match parse_text(text.trim()) {
+                Ok(text) => config.prompt = Some(text),
+                Err(e) => errors.push(anyhow!("Config line {} ('{}'): {}", line_number, line, e)),
+            }
+            continue;
+        }
+
+        if let Some(text) = line.strip_prefix("header ") {
+            match parse_text(text.trim()) {
+                Ok(text) => config.header = Some(text),
+                Err(e) => errors.push(anyhow!("Config line {} ('{}'): {}", line_number, line, e)),
+            }
+            continue;
+        }
+
+        if let Some(text) = line.strip_prefix("active-marker ") {
+            match parse_text(text.trim()) {
+                Ok(text) => config.active_marker = Some(text),
+                Err(e) => errors.push(anyhow!("Config line {} ('{}'): {}", line_number, line, e)),
+            }
+            continue;
+        }
+
+        if let Some(path) = line.strip_prefix("project ") {
+            match parse_line_path(path.trim(), home) {
+                Ok(path) => config.projects.push(path),
+                Err(e) => errors.push(anyhow!("Config line {} ('{}'): {}", line_number, line, e)),
+            }
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("tmux-socket-name ") {
+            config.tmux_server = Some(TmuxServer::Name(name.trim().to_string()));
+            continue;
+        }
+
+        if let Some(path) = line.strip_prefix("tmux-socket ") {
+            match parse_line_path(path.trim(), home) {
+                Ok(path) => config.tmux_server = Some(TmuxServer::Socket(path)),
+                Err(e) => errors.push(anyhow!("Config line {} ('{}'): {}", line_number, line, e)),
+            }
+            continue;
+        }
+
+        if let Some(retries) = line.strip_prefix("tmux-retries ") {
+            match retries.trim().parse() {
+                Ok(retries) => config.tmux_retries = Some(retries),
+                Err(_) => errors.push(anyhow!(
+                    "Config line {} ('{}'): Invalid number of retries.",
+                    line_number,
+                    line
+                )),
+            }
+            continue;
+        }
+
+        if let Some(window) = line.strip_prefix("window ") {
+            config.window = Some(window.trim().to_string());
+            continue;
+        }
+
+        if let Some(multiplexer) = line.strip_prefix("multiplexer ") {
+            match MultiplexerKind::from_str(multiplexer.trim(), false) {
+                Ok(multiplexer) => config.multiplexer = Some(multiplexer),
+                Err(_) => errors.push(anyhow!(
+                    "Config line {} ('{}'): Invalid multiplexer, expected 'tmux' or 'zellij'.",
+                    line_number,
+                    line
+                )),
+            }
+            continue;
+        }
+
+        if let Some(depth) = line.strip_prefix("default-depth ") {
+            match parse_depth(depth.trim()) {
+                Ok(depth) => default_depth = Some(depth),
+                Err(e) => errors.push(anyhow!("Config line {} ('{}'): {}", line_number, line, e)),
+            }
+            continue;
+        }
+
+        if let Some(timeout) = line.strip_prefix("scan-timeout ") {
+            match timeout
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Invalid scan timeout, expected a number of seconds."))
+                .and_then(parse_scan_timeout)
+            {
+                Ok(timeout) => config.scan_timeout = Some(timeout),
+                Err(e) => errors.push(anyhow!("Config line {} ('{}'): {}", line_number, line, e)),
+            }
+            continue;
+        }
+
+        if let Some(max_dirs) = line.strip_prefix("max-dirs ") {
+            match max_dirs.trim().parse() {
+                Ok(max_dirs) => config.max_dirs = Some(max_dirs),
+                Err(_) => errors.push(anyhow!(
+                    "Config line {} ('{}'): Invalid maximum number of directories.",
+                    line_number,
+                    line
+                )),
+            }
+            continue;
+        }
+
+        if let Some(scan_warning) = line.strip_prefix("scan-warning ") {
+            match scan_warning.trim().parse() {
+                Ok(scan_warning) => config.scan_warning = Some(scan_warning),
+                Err(_) => errors.push(anyhow!(
+                    "Config line {} ('{}'): Invalid number of directories.",
+                    line_number,
+                    line
+                )),
+            }
+            continue;
+        }
+
+        if let Some(sort) = line.strip_prefix("sort ") {
+            match sort.trim().parse() {
+                Ok(sort) => config.sort = Some(sort),
+                Err(e) => errors.push(anyhow!("Config line {} ('{}'): {}", line_number, line, e)),
+            }
+            continue;
+        }
+
+        if let Some(names) = line.strip_prefix("exclude ") {
+            config
+                .exclude
+                .extend(names.split_whitespace().map(String::from));
+            continue;
+        }
+
+        if let Some(patterns) = line.strip_prefix("search-only ") {
+            config
+                .search_only
+                .extend(patterns.split_whitespace().map(String::from));
+            continue;
+        }
+
+        if let Some(markers) = line.strip_prefix("markers ") {
+            config
+                .markers
+                .extend(markers.split_whitespace().map(String::from));
+            continue;
+        }
+
+        match parse_src_dir(line, home) {
+            Ok((src_dir, has_depth)) => {
+                if !has_depth {
+                    without_depth.push(config.src_dirs.len());
+                }
+                config.src_dirs.push(src_dir);
+            }
+            Err(e) => errors.push(anyhow!("Config line {} ('{}'): {}", line_number, line, e)),
+        }
+    }
+
+    if lenient {
+        for error in &errors {
+            eprintln!("Skipping {}", error);
+        }
+    } else if let Some(error) = errors.into_iter().next() {
+        return Err(error);
+    }
+
+    if let Some((min_depth, max_depth)) = default_depth {
+        for idx in without_depth {
+            config.src_dirs[idx].min_depth = min_depth;
+            config.src_dirs[idx].max_depth = max_depth;
+        }
+    }
+    for src_dir in &mut config.src_dirs {
+        src_dir.markers = config.markers.clone();
+        src_dir.search_only = config.search_only.clone();
+        src_dir.exclude = config.exclude.clone();
+    }
+
+    Ok(config)
+}
+
+/// Converts a scan timeout in seconds into a duration.
+fn parse_scan_timeout(secs: f64) -> Result<Duration> {
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| anyhow!("Invalid scan timeout, expected a number of seconds."))
+}
+
+/// Parses the text of a `prompt`, `header` or `active-marker` line, which is quoted like a path
+/// to keep the whitespace around it.
+fn parse_text(text: &str) -> Result<String> {
+    if !text.starts_with('"') {
+        return Ok(text.to_string());
+    }
+
+    match split_path(text)? {
+        (text, "") => Ok(text.into_owned()),
+        _ => Err(anyhow!("Unexpected text after the closing quote.")),
+    }
+}
+
+/// Parses the path of a `project` or `tmux-socket` line, which is quoted like the path of a
+/// source directory.
+fn parse_line_path(path: &str, home: &Path) -> Result<PathBuf> {
+    match split_path(path)? {
+        (path, "") => expand_path(&path, home),
+        _ => Err(anyhow!("Unexpected text after the path.")),
+    }
+}
+
+/// Parses a `path depth` config line, returning whether the line has a depth along with the
+/// source directory, which is searched at [`DEFAULT_DEPTH`] otherwise.
+fn parse_src_dir(line: &str, home: &Path) -> Result<(SrcDir, bool)> {
+    let (path, depth) = split_path(line)?;
+    let depth = depth.trim();
+    let (min_depth, max_depth) = if depth.is_empty() {
+        (DEFAULT_DEPTH, DEFAULT_DEPTH)
+    } else {
+        parse_depth(depth)?
+    };
+    let path = expand_path(&path, home)?;
+
+    let src_dir = SrcDir {
+        path,
+        min_depth,
+        max_depth,
+        ..Default::default()
+    };
+    Ok((src_dir, !depth.is_empty()))
+}
+
+/// Parses a search depth, which is either a single depth or an inclusive range like `1-2`, into
+/// its minimum and maximum.
+fn parse_depth(depth: &str) -> Result<(u8, u8)> {
+    let parse = |depth: &str| {
+        depth.trim().parse::<u8>().map_err(|_| {
+            anyhow!(
+                "Invalid search depth '{}', expected a whole number from 0 to {}.",
+                depth,
+                u8::MAX
+            )
+        })
+    };
+
+    let Some((min, max)) = depth.split_once('-') else {
+        let depth = parse(depth)?;
+        return Ok((depth, depth));
+    };
+
+    let (min, max) = (parse(min)?, parse(max)?);
+    if min > max {
+        return Err(anyhow!(
+            "Invalid search depth range '{}', the minimum is greater than the maximum.",
+            depth
+        ));
+    }
+
+    Ok((min, max))
+}
+
+/// Splits the path off the front of a config line, returning it along with the rest of the line.
+///
+/// A path containing whitespace can be wrapped in double quotes, inside of which `\"` and `\\`
+/// stand for a literal `"` and `\`.
+fn split_path(line: &str) -> Result<(Cow<'_, str>, &str)> {
+    let Some(quoted) = line.strip_prefix('"') else {
+        let (path, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        return Ok((Cow::from(path), rest));
+    };
+
+    let mut path = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => {
+                let rest = &quoted[idx + 1..];
+                if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+                    return Err(anyhow!("Expected whitespace after the closing quote."));
+                }
+                return Ok((Cow::from(path), rest));
+            }
+            '\\' => match chars.next() {
+                Some((_, c @ ('"' | '\\'))) => path.push(c),
+                Some((_, c)) => {
+                    path.push('\\');
+                    path.push(c);
+                }
+                None => break,
+            },
+            c => path.push(c),
+        }
+    }
+
+    Err(anyhow!("Unterminated quote in path."))
+}
+
+/// Expands a leading `~` to `home` and any `$VAR` or `${VAR}` to the value of that environment
+/// variable.
+pub fn expand_path(path: &str, home: &Path) -> Result<PathBuf> {
+    let mut expanded = String::with_capacity(path.len());
+
+    let rest = if path == "~" || path.starts_with("~/") {
+        expanded.push_str(&home.to_string_lossy());
+        &path[1..]
+    } else {
+        path
+    };
+
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+
+        let mut name = String::new();
+        if chars.next_if_eq(&'{').is_some() {
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => name.push(c),
+                    None => return Err(anyhow!("Unterminated `${{` in path '{}'.", path)),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                name.push(c);
+            }
+        }
+
+        if name.is_empty() {
+            // A lone `$` is kept as is
+            expanded.push('$');
+        } else {
+            expanded.push_str(&env_var(&name)?);
+        }
+    }
+
+    Ok(PathBuf::from(expanded))
+}
+
+/// Returns `$HOME`, falling back to `%USERPROFILE%` on Windows where `HOME` is usually unset.
+#[cfg(not(windows))]
+pub fn home_dir() -> Result<PathBuf> {
+    env_var("HOME").map(PathBuf::from)
+}
+
+#[cfg(windows)]
+pub fn home_dir() -> Result<PathBuf> {
+    env_var("HOME")
+        .or_else(|_| env_var("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+fn env_var(name: &str) -> Result<String> {
+    env::var(name).map_err(|_| anyhow!("Environment variable `{}` is not set.", name))
+}
+
+/// Strips a comment from a config line.
+///
+/// A comment is started by a `#` at the beginning of the line or by a `#` preceded by whitespace,
+/// so a `#` that is part of a path (e.g. `~/src/c#`) is left alone, as is any `#` inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut after_whitespace = true;
+
+    for (idx, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if in_quotes && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == '#' && !in_quotes && after_whitespace {
+            return &line[..idx];
+        }
+        after_whitespace = c.is_whitespace();
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOME: &str = "/home/user";
+
+    /// Reads `contents` as the config file named `file_name`.
+    fn read_config(file_name: &str, contents: &str) -> Result<Config> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(file_name);
+        fs::write(&path, contents)?;
+
+        read_config_file(&path, Path::new(HOME))
+    }
+
+    fn src_dir(path: &str, min_depth: u8, max_depth: u8) -> SrcDir {
+        SrcDir {
+            path: PathBuf::from(path),
+            min_depth,
+            max_depth,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reads_src_dirs() {
+        let config = read_config(
+            "find_project.conf",
+            "# comment\n~/src 2\n\n/opt/projects 1-3 # trailing comment\n\"~/My Projects\" 1\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.src_dirs,
+            [
+                src_dir("/home/user/src", 2, 2),
+                src_dir("/opt/projects", 1, 3),
+                src_dir("/home/user/My Projects", 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_crlf_config() {
+        let config = read_config(
+            "find_project.conf",
+            "\u{feff}# comment\r\n~/src 2\r\n\r\nhidden\r\n/opt/projects 1-3 # trailing comment\r\n\"~/My Projects\" 1\r\n",
+        )
+        .unwrap();
+
+        assert!(config.hidden);
+        assert_eq!(
+            config.src_dirs,
+            [
+                src_dir("/home/user/src", 2, 2),
+                src_dir("/opt/projects", 1, 3),
+                src_dir("/home/user/My Projects", 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_quoted_prompt() {
+        let config = read_config(
+            "find_project.conf",
+            "prompt \"project> \"\nheader {count} projects\nactive-marker \"\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.prompt.as_deref(), Some("project> "));
+        assert_eq!(config.header.as_deref(), Some("{count} projects"));
+        assert_eq!(config.active_marker.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn depth_defaults_to_one() {
+        let config = read_config("find_project.conf", "~/src\n\"~/My Projects\"\n").unwrap();
+
+        assert_eq!(
+            config.src_dirs,
+            [
+                src_dir("/home/user/src", 1, 1),
+                src_dir("/home/user/My Projects", 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn default_depth_applies_to_src_dirs_without_depth() {
+        let config =
+            read_config("find_project.conf", "~/src\n~/work 3\ndefault-depth 1-2\n").unwrap();
+
+        assert_eq!(
+            config.src_dirs,
+            [
+                src_dir("/home/user/src", 1, 2),
+                src_dir("/home/user/work", 3, 3)
+            ]
+        );
+
+        let config = read_config(
+            "find_project.toml",
+            "default-depth = 2\n\n[[source]]\npath = \"~/src\"\n\n[[source]]\npath = \"~/work\"\ndepth = 3\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.src_dirs,
+            [
+                src_dir("/home/user/src", 2, 2),
+                src_dir("/home/user/work", 3, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn uses_default_src_dir_without_src_dirs() {
+        let config = read_config("find_project.conf", "# just a comment\n").unwrap();
+
+        assert_eq!(config.src_dirs, [src_dir("/home/user/src", 2, 2)]);
+    }
+
+    #[test]
+    fn uses_default_src_dir_for_empty_file() {
+        let config = read_config("find_project.conf", "").unwrap();
+
+        assert_eq!(config.src_dirs, [src_dir("/home/user/src", 2, 2)]);
+    }
+
+    #[test]
+    fn no_default_disables_default_src_dir() {
+        let config = read_config("find_project.conf", "no-default\n").unwrap();
+
+        assert!(config.src_dirs.is_empty());
+    }
+
+    #[test]
+    fn default_src_dir_is_relative_to_home() {
+        let home = Path::new(HOME);
+        let default_src_dir = |value: &str| default_src_dir(Some(OsStr::new(value)), home);
+
+        assert_eq!(
+            default_src_dir("code").unwrap(),
+            Some(src_dir("/home/user/code", 2, 2))
+        );
+        assert_eq!(
+            default_src_dir("/opt/projects 1-3").unwrap(),
+            Some(src_dir("/opt/projects", 1, 3))
+        );
+        assert_eq!(default_src_dir("").unwrap(), None);
+        assert!(default_src_dir("code deep").is_err());
+        assert_eq!(
+            super::default_src_dir(None, home).unwrap(),
+            Some(src_dir("/home/user/src", 2, 2))
+        );
+    }
+
+    #[test]
+    fn default_src_dir_isnt_added_to_src_dirs() {
+        let config = read_config("find_project.conf", "/opt/projects 1\n").unwrap();
+
+        assert_eq!(config.src_dirs, [src_dir("/opt/projects", 1, 1)]);
+    }
+
+    #[test]
+    fn malformed_line_is_an_error() {
+        let err = read_config("find_project.conf", "~/src 2\n~/work 1 2\n/opt 1\n").unwrap_err();
+
+        assert!(
+            err.to_string().starts_with("Config line 2 ('~/work 1 2')"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn lenient_skips_malformed_lines() {
+        let config = read_config(
+            "find_project.conf",
+            "lenient\n~/src two\n/opt/projects 1\n\"~/work\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.src_dirs, [src_dir("/opt/projects", 1, 1)]);
+    }
+
+    #[test]
+    fn missing_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert!(read_config_file(dir.path().join("find_project.conf"), Path::new(HOME)).is_err());
+    }
+
+    #[test]
+    fn reads_toml_src_dirs() {
+        let config = read_config(
+            "find_project.toml",
+            "[[source]]\npath = \"~/src\"\ndepth = 2\n\n[[source]]\npath = \"/opt/projects\"\ndepth = \"1-3\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.src_dirs,
+            [
+                src_dir("/home/user/src", 2, 2),
+                src_dir("/opt/projects", 1, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn globs_are_expanded_to_matching_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["team-a", "team-b", ".team-hidden", "other"] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        fs::write(dir.path().join("team-file"), "").unwrap();
+
+        let config = read_config(
+            "find_project.conf",
+            &format!("{0}/team-* 1\n{0}/missing-* 1\n", dir.path().display()),
+        )
+        .unwrap();
+
+        let paths = config
+            .src_dirs
+            .iter()
+            .map(|src_dir| src_dir.path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [dir.path().join("team-a"), dir.path().join("team-b")]
+        );
+        assert!(glob_matches(&['?', 'b', '*'], &['a', 'b']));
+        assert!(!glob_matches(&['*', 'c'], &['a', 'b']));
+    }
+
+    #[test]
+    fn reads_tmux_server() {
+        let line_config = read_config(
+            "find_project.conf",
+            "tmux-socket ~/tmux.sock\ntmux-retries 5\n",
+        )
+        .unwrap();
+        let toml_config = read_config(
+            "find_project.toml",
+            "tmux-socket-name = \"work\"\ntmux-retries = 0\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            line_config.tmux_server,
+            Some(TmuxServer::Socket(PathBuf::from("/home/user/tmux.sock")))
+        );
+        assert_eq!(
+            toml_config.tmux_server,
+            Some(TmuxServer::Name("work".to_string()))
+        );
+        assert_eq!(line_config.tmux_retries, Some(5));
+        assert_eq!(toml_config.tmux_retries, Some(0));
+        assert!(read_config(
+            "find_project.toml",
+            "tmux-socket-name = \"work\"\ntmux-socket = \"/tmp/tmux.sock\"\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn reads_explicit_projects() {
+        let line_config = read_config(
+            "find_project.conf",
+            "~/src 2\nproject ~/dotfiles\nproject \"/opt/My Repo\"\n",
+        )
+        .unwrap();
+        let toml_config = read_config(
+            "find_project.toml",
+            "[[project]]\npath = \"~/dotfiles\"\n\n[[project]]\npath = \"/opt/My Repo\"\n",
+        )
+        .unwrap();
+
+        let projects = [
+            PathBuf::from("/home/user/dotfiles"),
+            PathBuf::from("/opt/My Repo"),
+        ];
+        assert_eq!(line_config.projects, projects);
+        assert_eq!(toml_config.projects, projects);
+    }
+
+    #[test]
+    fn user_config_is_layered_on_system_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("system.conf");
+        let user = dir.path().join("user.conf");
+        fs::write(&system, "/opt/shared 1\n/opt/projects 1\nsort name\n").unwrap();
+        fs::write(&user, "/opt/projects 2\n~/src 2\n").unwrap();
+        let missing = dir.path().join("missing.conf");
+
+        let config = read_config_files(&[system, missing, user], Path::new(HOME)).unwrap();
+
+        assert_eq!(config.sort, Some(SortOrder::Name));
+        assert_eq!(
+            config.src_dirs,
+            [
+                src_dir("/opt/shared", 1, 1),
+                src_dir("/opt/projects", 2, 2),
+                src_dir("/home/user/src", 2, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn config_flag_takes_precedence_over_env_var() {
+        let flag = Path::new("flag.conf");
+        let env_path = || Some(OsString::from("env.conf"));
+
+        assert_eq!(
+            config_override(Some(flag), env_path()),
+            Some((PathBuf::from("flag.conf"), "--config"))
+        );
+        assert_eq!(
+            config_override(None, env_path()),
+            Some((PathBuf::from("env.conf"), "$FIND_PROJECT_CONFIG"))
+        );
+        assert_eq!(config_override(None, Some(OsString::new())), None);
+        assert_eq!(config_override(None, None), None);
+    }
+
+    #[test]
+    fn config_path_is_resolved_against_cwd_and_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(cwd.join("configs")).unwrap();
+        fs::write(cwd.join("configs/find_project.conf"), "~/src 2\n").unwrap();
+
+        let target = cwd.join("configs/find_project.conf");
+        assert_eq!(
+            resolve_config_path(Path::new("configs/../configs/find_project.conf"), &cwd),
+            target
+        );
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("configs/find_project.conf", cwd.join("find_project.conf"))
+                .unwrap();
+            assert_eq!(
+                resolve_config_path(Path::new("find_project.conf"), &cwd),
+                target
+            );
+        }
+        assert_eq!(
+            resolve_config_path(&target, Path::new("/elsewhere")),
+            target
+        );
+        assert_eq!(
+            resolve_config_path(Path::new("new.conf"), &cwd),
+            cwd.join("new.conf")
+        );
+    }
+
+    #[test]
+    fn added_src_dirs_are_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let projects = dir.path().join("My Projects");
+        fs::create_dir(&projects).unwrap();
+        let projects = projects.to_str().unwrap();
+
+        for file_name in ["find_project.conf", "find_project.toml"] {
+            let path = dir.path().join("config").join(file_name);
+
+            add_src_dir(&path, projects, 1, Path::new(HOME)).unwrap();
+            assert!(add_src_dir(&path, projects, 2, Path::new(HOME)).is_err());
+
+            let config = read_config_file(&path, Path::new(HOME)).unwrap();
+            assert_eq!(config.src_dirs, [src_dir(projects, 1, 1)]);
+        }
+    }
+
+    #[test]
+    fn src_dirs_are_added_after_a_byte_order_mark() {
+        let dir = tempfile::tempdir().unwrap();
+        let (work, projects) = (dir.path().join("work"), dir.path().join("projects"));
+        fs::create_dir(&work).unwrap();
+        fs::create_dir(&projects).unwrap();
+        let (work, projects) = (work.to_str().unwrap(), projects.to_str().unwrap());
+
+        for (file_name, contents) in [
+            ("find_project.conf", format!("\u{feff}{} 1\r\n", work)),
+            (
+                "find_project.toml",
+                format!("\u{feff}[[source]]\r\npath = '{}'\r\ndepth = 1\r\n", work),
+            ),
+        ] {
+            let path = dir.path().join(file_name);
+            fs::write(&path, contents).unwrap();
+
+            assert!(add_src_dir(&path, work, 1, Path::new(HOME)).is_err());
+            add_src_dir(&path, projects, 2, Path::new(HOME)).unwrap();
+
+            let config = read_config_file(&path, Path::new(HOME)).unwrap();
+            assert_eq!(
+                config.src_dirs,
+                [src_dir(work, 1, 1), src_dir(projects, 2, 2)]
+            );
+        }
+    }
+}
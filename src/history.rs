@@ -0,0 +1,89 @@
+//! Remembering which projects were opened, so that the recently opened ones can be listed first.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+
+use crate::cache::write_cache_file;
+use crate::path_from_bytes;
+
+/// How many projects the history remembers, dropping the ones opened longest ago.
+pub const HISTORY_SIZE: usize = 100;
+
+/// Reads when each project in the history at `path` was last opened, in seconds since the Unix
+/// epoch, which is empty if there is no history.
+///
+/// The history has a `<secs> <path>` line for each project, skipping the ones that can't be
+/// parsed.
+pub fn read_history(path: &Path) -> HashMap<PathBuf, f64> {
+    let Ok(contents) = fs::read(path) else {
+        return HashMap::new();
+    };
+
+    contents
+        .split(|&byte| byte == b'\n')
+        .filter_map(|line| {
+            let separator = line.iter().position(|&byte| byte == b' ')?;
+            let (secs, path) = (&line[..separator], &line[separator + 1..]);
+            let secs = std::str::from_utf8(secs).ok()?.parse().ok()?;
+            Some((path_from_bytes(path), secs))
+        })
+        .collect()
+}
+
+/// Records that the project at `project_path` was opened now in the history at `path`.
+pub fn record_opened(path: &Path, project_path: &Path) -> Result<()> {
+    let mut history = read_history(path).into_iter().collect::<Vec<_>>();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+
+    history.retain(|(path, _)| path != project_path);
+    history.push((project_path.to_path_buf(), now));
+    history.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    history.truncate(HISTORY_SIZE);
+
+    let mut contents = Vec::new();
+    for (path, secs) in &history {
+        contents.extend_from_slice(format!("{:.3} ", secs).as_bytes());
+        contents.extend_from_slice(path.as_os_str().as_encoded_bytes());
+        contents.push(b'\n');
+    }
+
+    write_cache_file(path, &contents)
+}
+
+/// Removes the history at `path`, if there is one.
+pub fn clear_history(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_projects_are_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+
+        record_opened(&path, Path::new("/src/a")).unwrap();
+        record_opened(&path, Path::new("/src/b c")).unwrap();
+        record_opened(&path, Path::new("/src/a")).unwrap();
+
+        let history = read_history(&path);
+        assert_eq!(history.len(), 2);
+        assert!(history[Path::new("/src/a")] >= history[Path::new("/src/b c")]);
+
+        clear_history(&path).unwrap();
+        assert!(read_history(&path).is_empty());
+    }
+}
@@ -0,0 +1,36 @@
+//! Finds the projects in the configured source directories and opens sessions for them in a
+//! terminal multiplexer.
+
+#[cfg(unix)]
+use std::ffi::OsStr;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+pub mod cache;
+pub mod completions;
+pub mod config;
+pub mod history;
+pub mod multiplexer;
+pub mod scan;
+pub mod status;
+pub mod zoxide;
+
+pub use config::{read_config_file, Config, SrcDir};
+pub use scan::{scan_projects, Project, ScanOptions};
+
+/// The name of the directories holding the config and cache.
+pub const PROJECT_NAME: &str = "find_project";
+
+/// Converts the bytes of a path read from fzf or the cache back into a path.
+///
+/// Paths are only guaranteed to be UTF-8 outside of Unix, which is how they are decoded there.
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
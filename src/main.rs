@@ -1,251 +1,1846 @@
 use std::borrow::Cow;
+use std::collections::HashSet;
 use std::env;
-use std::ffi::OsStr;
-use std::fs::{self, ReadDir};
-use std::io::Write;
-use std::os::unix::ffi::OsStrExt;
-use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::str::FromStr;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
 
 use anyhow::{anyhow, Result};
+use clap::{CommandFactory, Parser, ValueEnum};
+use serde::Serialize;
 
-const PROJECT_NAME: &'static str = "find_project";
+use fp_rs::cache::{cache_key, cache_path, read_cache, write_cache};
+use fp_rs::completions::{self, Shell};
+use fp_rs::config::{
+    add_src_dir, check_project, check_src_dir, config_home, config_override,
+    create_commented_config, create_default_config, expand_path, find_config_file, home_dir,
+    read_config_files, read_line_config, resolve_config_path, system_config_dir, CONFIG_ENV_VAR,
+    DEFAULT_DEPTH, DEFAULT_SRC_DIR_ENV_VAR,
+};
+use fp_rs::history::{clear_history, read_history, record_opened};
+use fp_rs::multiplexer::{
+    CommandFailed, Layout, Multiplexer, MultiplexerKind, SessionNameTemplate, Tmux, TmuxServer,
+    Zellij, DEFAULT_SESSION_NAME_REPLACEMENT, DEFAULT_TMUX_RETRIES,
+};
+use fp_rs::scan::{
+    estimate_dirs, rank_projects, scan_projects, sort_projects, Scan, ScanBudget, ScanOptions,
+    SortOrder, DEFAULT_MAX_DIRS, DEFAULT_SCAN_TIMEOUT, DEFAULT_SCAN_WARNING,
+};
+use fp_rs::status;
+use fp_rs::zoxide::query_scores;
+use fp_rs::{path_from_bytes, Config, Project, SrcDir, PROJECT_NAME};
 
-const FZF_BIN: &'static str = "/usr/bin/fzf";
-const TMUX_BIN: &'static str = "/usr/bin/tmux";
+/// Where the config is read from and the exit codes, shown after the flags in `--help`.
+const AFTER_HELP: &str = "\
+The config is read from $XDG_CONFIG_HOME/find_project/find_project.toml or find_project.conf,
+where $XDG_CONFIG_HOME defaults to ~/.config. It's created with an example on the first run.
+A config in /etc/find_project is read before it, with the user's config taking precedence.
 
-// FIX: Server crashing unexpectedly when connecting to a new session
+Exits with 2 if no project matches the query, 3 if a binary that is needed isn't found, 4 if the
+config can't be read and 130 if fzf is closed without selecting a project. A failing tmux or
+Zellij command exits with its own code.";
 
-/// A directory holding projects.
-#[derive(Debug)]
-struct SrcDir {
-    /// The *full* path to the directory containing projects.
-    path: PathBuf,
-    /// The number of directories between the `path` to the actual projects
-    search_depth: u8,
+/// The shell opened in projects when `$SHELL` isn't set.
+#[cfg(not(windows))]
+const DEFAULT_SHELL: &str = "sh";
+#[cfg(windows)]
+const DEFAULT_SHELL: &str = "cmd.exe";
+
+/// The editor opened in projects with `--edit` when `$EDITOR` isn't set.
+const DEFAULT_EDITOR: &str = "vi";
+
+const FZF_BIN: &str = "fzf";
+const TMUX_BIN: &str = "tmux";
+const ZELLIJ_BIN: &str = "zellij";
+const ZOXIDE_BIN: &str = "zoxide";
+
+/// Starts the line naming a source directory above its projects with `--group`, which can't be
+/// mistaken for a path or a session.
+const GROUP_HEADER_PREFIX: &str = "── ";
+
+/// Shown instead of the source directory of the projects listed in the config.
+const EXPLICIT_SOURCE: &str = "explicit";
+
+/// Shown in fzf before the projects that have an active session.
+const DEFAULT_ACTIVE_MARKER: &str = "●";
+
+/// The name of the file in the cache directory holding the recently opened projects.
+const HISTORY_FILE: &str = "history";
+
+/// The number of lines of a project's README shown in the fzf preview.
+const PREVIEW_README_LINES: u8 = 20;
+
+/// Quickly switch between projects using fzf and tmux.
+#[derive(Debug, Parser)]
+#[command(version, after_help = AFTER_HELP)]
+struct Args {
+    /// Print the full path of each project, one per line, instead of selecting one
+    #[arg(long)]
+    list: bool,
+    /// How to print the projects with `--list`
+    #[arg(long, value_enum, default_value_t, requires = "list")]
+    format: ListFormat,
+    /// Only list the projects in an up to date project cache, printing nothing instead of
+    /// scanning or creating the config, for completing project names in a shell
+    #[arg(
+        long,
+        hide = true,
+        requires = "list",
+        conflicts_with_all = ["no_cache", "rebuild_cache", "stdin"]
+    )]
+    list_cached: bool,
+    /// Search every source directory at this depth instead of the configured ones
+    #[arg(long, value_name = "N")]
+    depth: Option<u8>,
+    /// Search hidden directories, which are skipped by default
+    #[arg(long)]
+    hidden: bool,
+    /// Search symlinked directories, which are skipped by default
+    #[arg(long)]
+    follow_symlinks: bool,
+    /// List git repositories as projects at any depth, without searching inside them
+    #[arg(long)]
+    git_roots: bool,
+    /// Skip the source directories in this directory, which can be given more than once
+    #[arg(long, value_name = "PATH")]
+    exclude: Vec<String>,
+    /// Only search the source directories in this directory, which can be given more than once
+    #[arg(long, value_name = "PATH")]
+    only: Vec<String>,
+    /// Scan for projects without reading or writing the project cache
+    #[arg(long, conflicts_with = "rebuild_cache")]
+    no_cache: bool,
+    /// Scan for projects even if the project cache is up to date
+    #[arg(long)]
+    rebuild_cache: bool,
+    /// How to order the projects [default: mtime]
+    #[arg(long, value_enum)]
+    sort: Option<SortOrder>,
+    /// Select several projects, starting sessions for all of them and switching to the last
+    #[arg(short, long)]
+    multi: bool,
+    /// Open the selected project in `$EDITOR` instead of a multiplexer session
+    #[arg(long, conflicts_with_all = ["multi", "cd"])]
+    edit: bool,
+    /// Open the editor in a new window of the current tmux session instead
+    #[arg(long, requires = "edit")]
+    edit_window: bool,
+    /// List the projects in fzf under a line naming the source directory they were found in
+    #[arg(long, conflicts_with_all = ["list", "name"])]
+    group: bool,
+    /// Start fzf with this query
+    #[arg(short, long, value_name = "QUERY", conflicts_with_all = ["list", "name"])]
+    query: Option<String>,
+    /// Select the only project matching the query without opening fzf
+    #[arg(long, conflicts_with_all = ["list", "name"])]
+    select_1: bool,
+    /// Exit without opening fzf if no project matches the query
+    #[arg(long, conflicts_with_all = ["list", "name"])]
+    exit_0: bool,
+    /// Open a shell in the selected project instead of a multiplexer session
+    #[arg(long, conflicts_with = "multi")]
+    cd: bool,
+    /// Use the tmux server with this socket name, as with `tmux -L`
+    #[arg(long, value_name = "NAME", conflicts_with = "tmux_socket")]
+    tmux_socket_name: Option<String>,
+    /// Use the tmux server with this socket, as with `tmux -S`
+    #[arg(long, value_name = "PATH")]
+    tmux_socket: Option<PathBuf>,
+    /// Create the session without switching to it and print its name
+    #[arg(long, visible_alias = "print-session", conflicts_with_all = ["cd", "edit", "list"])]
+    detach: bool,
+    /// Only switch to projects that already have a session, failing instead of creating one
+    #[arg(long, conflicts_with_all = ["cd", "edit", "list"])]
+    attach_only: bool,
+    /// Print the multiplexer commands that would change sessions instead of running them
+    #[arg(long, conflicts_with = "cd")]
+    dry_run: bool,
+    /// Show the source directory of each project after its path in fzf
+    #[arg(long)]
+    show_source: bool,
+    /// The prompt shown by fzf
+    #[arg(long, value_name = "TEXT", conflicts_with_all = ["list", "name"])]
+    prompt: Option<String>,
+    /// The header shown by fzf above the projects, where `{count}` is the number of projects
+    #[arg(long, value_name = "TEXT", conflicts_with_all = ["list", "name"])]
+    header: Option<String>,
+    /// Fail if a source directory doesn't exist or a directory can't be read instead of skipping it
+    #[arg(long)]
+    strict: bool,
+    /// Forget which projects were opened recently, then exit
+    #[arg(long)]
+    clear_history: bool,
+    /// Don't print what is being done, like which session is switched to
+    #[arg(long)]
+    quiet: bool,
+    /// Print why things are done, like which directories were scanned and which sessions exist
+    #[arg(short = 'v', long)]
+    debug: bool,
+    /// Read the config from this file instead of `$FIND_PROJECT_CONFIG` or the one in
+    /// `$XDG_CONFIG_HOME`
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Read the source directories from stdin, one `path depth` line each, instead of the config
+    #[arg(long, conflicts_with_all = ["config", "print_config_path"])]
+    stdin: bool,
+    /// Print the paths of the config files that would be read and whether they exist, then exit
+    #[arg(long)]
+    print_config_path: bool,
+    /// Switch to the project in the directory with this name instead of selecting one with fzf,
+    /// or with this path suffix like `team-a/api` if no directory has the name
+    #[arg(conflicts_with_all = ["list", "multi"])]
+    name: Option<String>,
+    /// How the case of the project name is matched
+    #[arg(long, value_enum, default_value_t, requires = "name")]
+    match_case: MatchCase,
+    #[command(subcommand)]
+    command: Option<Subcommand>,
 }
 
-/// A project directory
-#[derive(Debug)]
-struct Project {
-    /// The *full* path to the project root directory. (Including the directory name itself)
-    inner: PathBuf,
+/// How the projects are printed with `--list`.
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+enum ListFormat {
+    /// The full path of each project, one per line
+    #[default]
+    Paths,
+    /// An array of objects with the name, path and source directory of each project
+    Json,
+    /// The name and full path of each project separated by a tab, one per line
+    Tsv,
 }
 
-impl Project {
-    fn name(&self) -> Option<&str> {
-        if let Some(s) = self.inner.file_name() {
-            s.to_str()
+/// How the case of a project name given on the command line is matched.
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+enum MatchCase {
+    /// Ignore case unless the name contains an uppercase letter
+    #[default]
+    Smart,
+    /// Ignore case
+    Ignore,
+    /// Match case exactly
+    Exact,
+}
+
+impl MatchCase {
+    /// Returns whether the project name `candidate` matches the given `name`.
+    fn matches(self, name: &str, candidate: &str) -> bool {
+        let ignore_case = match self {
+            MatchCase::Smart => !name.chars().any(char::is_uppercase),
+            MatchCase::Ignore => true,
+            MatchCase::Exact => false,
+        };
+
+        if ignore_case {
+            name.to_lowercase() == candidate.to_lowercase()
         } else {
-            None
+            name == candidate
         }
     }
+}
+
+/// A project printed with `--format json`.
+#[derive(Debug, Serialize)]
+struct ListedProject<'a> {
+    name: Option<&'a str>,
+    path: Cow<'a, str>,
+    /// The source directory the project was found in.
+    source: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Subcommand {
+    /// Add a source directory to the config
+    Add {
+        /// The directory holding projects
+        path: String,
+        /// The number of directories between it and the projects
+        #[arg(default_value_t = DEFAULT_DEPTH)]
+        depth: u8,
+    },
+    /// Kill the sessions of projects that no longer exist
+    Clean {
+        /// Print the sessions that would be killed without killing them
+        #[arg(long)]
+        dry_run: bool,
+        /// Also kill sessions that weren't started for a project
+        #[arg(long)]
+        all: bool,
+    },
+    /// Select an active session with fzf and kill it, after asking
+    Kill {
+        /// Kill the session without asking
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Print the version, the binaries and config files used and the environment, without
+    /// scanning, to help tell why something doesn't work
+    Doctor,
+    /// Print a script completing the flags and project names for a shell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
 
-    fn full_path(&self) -> &Path {
-        self.inner.as_path()
+/// The outcomes that exit with their own code, so that scripts can tell them apart from errors.
+#[derive(Debug)]
+enum Failure {
+    /// No project matched the query, which fzf reports with exit code 1.
+    NoMatch,
+    /// fzf was closed without selecting a project, which it reports with exit code 130.
+    Aborted,
+    /// A binary that is needed, like fzf or tmux, couldn't be found.
+    MissingBinary(String),
+    /// The config couldn't be read, or names a source directory that can't be used.
+    Config(String),
+}
+
+impl Failure {
+    fn exit_code(&self) -> u8 {
+        match self {
+            Failure::NoMatch => 2,
+            Failure::MissingBinary(_) => 3,
+            Failure::Config(_) => 4,
+            Failure::Aborted => 130,
+        }
     }
 }
 
-fn main() -> Result<()> {
-    let mut config_file_path = PathBuf::from_str(env::var("XDG_CONFIG_HOME")?.as_str())?;
-    let config_file_name = format!("{}.conf", PROJECT_NAME);
-    config_file_path.push(PROJECT_NAME);
-    config_file_path.push(config_file_name);
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::NoMatch => write!(f, "No project matched your query."),
+            Failure::Aborted => write!(f, "You did not select project."),
+            Failure::MissingBinary(message) | Failure::Config(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Wraps an error reading the config, keeping its causes in the message.
+fn config_failure(err: anyhow::Error) -> anyhow::Error {
+    Failure::Config(format!("{:#}", err)).into()
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            // Printed the same way as returning the error from `main` would
+            eprintln!("Error: {:?}", err);
+            if let Some(failure) = err.downcast_ref::<Failure>() {
+                return ExitCode::from(failure.exit_code());
+            }
+            // A failing multiplexer command is exited with its own code
+            match err
+                .downcast_ref::<CommandFailed>()
+                .and_then(|failed| failed.code)
+                .and_then(|code| u8::try_from(code).ok())
+            {
+                Some(code) if code != 0 => ExitCode::from(code),
+                _ => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    let args = Args::parse();
+    status::init(args.quiet, args.debug);
+
+    if let Some(Subcommand::Completions { shell }) = args.command {
+        // Completed under the name it was run as, in case it was installed under another name
+        let bin_name = env::args_os()
+            .next()
+            .and_then(|arg| {
+                Path::new(&arg)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| Args::command().get_name().to_string());
+        print!(
+            "{}",
+            completions::generate(shell, &mut Args::command(), &bin_name)?
+        );
+        return Ok(());
+    }
+
+    if args.clear_history {
+        clear_history(&cache_path(HISTORY_FILE)?)?;
+        status::print("Cleared the history.");
+        return Ok(());
+    }
+
+    let config_override = config_override(args.config.as_deref(), env::var_os(CONFIG_ENV_VAR));
+    let config_file_path = match &config_override {
+        Some((path, _)) => resolve_config_path(path, &env::current_dir()?),
+        None => find_config_file(&config_home()?.join(PROJECT_NAME)),
+    };
 
-    let src_dirs = read_config_file(config_file_path)?;
+    if let Some(Subcommand::Add { path, depth }) = &args.command {
+        let src_dir = add_src_dir(&config_file_path, path, *depth, &home_dir()?)?;
+        status::print(&format!(
+            "Added '{}' with a depth of {} to '{}'.",
+            src_dir.path.display(),
+            depth,
+            config_file_path.display()
+        ));
+        return Ok(());
+    }
+
+    if let Some(Subcommand::Doctor) = &args.command {
+        return doctor(&config_file_paths(&config_file_path));
+    }
 
-    let projects = src_dirs
+    let mut config = if args.stdin {
+        read_line_config(io::stdin().lock(), &home_dir()?).map_err(config_failure)?
+    } else {
+        let config_file_paths = config_file_paths(&config_file_path);
+
+        if args.print_config_path {
+            for path in &config_file_paths {
+                let state = if path.is_file() {
+                    "exists"
+                } else {
+                    "does not exist"
+                };
+                println!("{} ({})", path.display(), state);
+            }
+            return Ok(());
+        }
+
+        if let Some((_, given_with)) = config_override {
+            if let Ok(target) = fs::read_link(&config_file_path) {
+                // Symlinks are resolved unless they're broken
+                return Err(Failure::Config(format!(
+                    "Config file '{}' from {} is a symlink to '{}', which does not exist.",
+                    config_file_path.display(),
+                    given_with,
+                    target.display()
+                ))
+                .into());
+            }
+            if !config_file_path.is_file() {
+                return Err(Failure::Config(format!(
+                    "Config file '{}' from {} does not exist.",
+                    config_file_path.display(),
+                    given_with
+                ))
+                .into());
+            }
+        } else if !config_file_path.exists() {
+            // Nothing can be cached without a config, and completing shouldn't create one
+            if args.list_cached {
+                return Ok(());
+            }
+            // Asking needs someone to answer, so scripts get the default config instead
+            if io::stdin().is_terminal() && io::stderr().is_terminal() {
+                set_up_config(&config_file_path).map_err(config_failure)?;
+            } else {
+                create_default_config(&config_file_path).map_err(config_failure)?;
+            }
+            status::print(&format!(
+                "Created config file '{}'.",
+                config_file_path.display()
+            ));
+        }
+
+        for path in config_file_paths.iter().filter(|path| path.is_file()) {
+            status::debug(&format!("Reading the config file '{}'", path.display()));
+        }
+        read_config_files(&config_file_paths, &home_dir()?).map_err(config_failure)?
+    };
+
+    status::set_bell(config.bell);
+
+    if let Some(name) = args.tmux_socket_name {
+        config.tmux_server = Some(TmuxServer::Name(name));
+    } else if let Some(path) = args.tmux_socket {
+        config.tmux_server = Some(TmuxServer::Socket(path));
+    }
+
+    if let Some(depth) = args.depth {
+        for src_dir in &mut config.src_dirs {
+            src_dir.min_depth = depth;
+            src_dir.max_depth = depth;
+        }
+    }
+
+    if !args.exclude.is_empty() || !args.only.is_empty() {
+        let home = home_dir()?;
+        // Compared canonically so that paths through symlinks or with `..` match too
+        let expand = |paths: &[String]| {
+            paths
+                .iter()
+                .map(|path| Ok(canonical_path(&expand_path(path, &home)?)))
+                .collect::<Result<Vec<_>>>()
+        };
+        let excluded = expand(&args.exclude)?;
+        let only = expand(&args.only)?;
+
+        for (path, only) in args.only.iter().zip(&only) {
+            let matches = config
+                .src_dirs
+                .iter()
+                .any(|src_dir| canonical_path(&src_dir.path).starts_with(only));
+            if !matches {
+                eprintln!("No source directory is in '{}'.", path);
+            }
+        }
+
+        config.src_dirs.retain(|src_dir| {
+            let path = canonical_path(&src_dir.path);
+            !excluded.iter().any(|excluded| path.starts_with(excluded))
+                && (only.is_empty() || only.iter().any(|only| path.starts_with(only)))
+        });
+    }
+
+    // Checked before scanning so that typos in the config are reported even with a cache
+    let mut src_dirs = Vec::with_capacity(config.src_dirs.len());
+    for src_dir in config.src_dirs {
+        match check_src_dir(&src_dir) {
+            Ok(()) => src_dirs.push(src_dir),
+            Err(e) if args.strict => return Err(config_failure(e)),
+            Err(e) => eprintln!("{} Skipping it.", e),
+        }
+    }
+    config.src_dirs = src_dirs;
+    let mut explicit_projects = Vec::with_capacity(config.projects.len());
+    for path in config.projects {
+        match check_project(&path) {
+            Ok(()) => explicit_projects.push(path),
+            Err(e) if args.strict => return Err(config_failure(e)),
+            Err(e) => eprintln!("{} Skipping it.", e),
+        }
+    }
+    config.projects = explicit_projects;
+
+    // Resolved before scanning so that a missing binary is reported without waiting for a scan
+    let selects_with_fzf = !args.list
+        && args.name.is_none()
+        && matches!(args.command, None | Some(Subcommand::Kill { .. }));
+    // `--detach` and `--attach-only` ask for a session, so they take precedence over a `cd` line
+    let opens_shell = args.cd || (config.cd && !args.detach && !args.attach_only);
+    let uses_multiplexer =
+        !args.list && (args.command.is_some() || args.edit_window || !(opens_shell || args.edit));
+    let fzf_bin = selects_with_fzf
+        .then(|| resolve_binary(FZF_BIN, config.fzf.as_deref()))
+        .transpose();
+    let multiplexer = uses_multiplexer
+        .then(|| open_multiplexer(&config, args.dry_run))
+        .transpose();
+    let (fzf_bin, multiplexer) = match (fzf_bin, multiplexer) {
+        (Ok(fzf_bin), Ok(multiplexer)) => (fzf_bin, multiplexer),
+        (fzf_bin, multiplexer) => {
+            let missing = [fzf_bin.err(), multiplexer.err()]
+                .into_iter()
+                .flatten()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>();
+            return Err(Failure::MissingBinary(missing.join("\n")).into());
+        }
+    };
+
+    // Killing a session picks from the sessions instead of the projects, which needn't be found
+    if let (Some(Subcommand::Kill { yes }), Some(multiplexer), Some(fzf_bin)) =
+        (&args.command, &multiplexer, &fzf_bin)
+    {
+        return kill_session(multiplexer.as_ref(), fzf_bin, *yes);
+    }
+
+    let scan_opts = ScanOptions {
+        hidden: args.hidden || config.hidden,
+        follow_symlinks: args.follow_symlinks || config.follow_symlinks,
+        git_roots: args.git_roots || config.git_roots,
+        exclude_ignore_case: config.exclude_ignore_case,
+    };
+
+    let scan_budget = ScanBudget::new(
+        Some(config.scan_timeout.unwrap_or(DEFAULT_SCAN_TIMEOUT))
+            .filter(|timeout| !timeout.is_zero()),
+        Some(config.max_dirs.unwrap_or(DEFAULT_MAX_DIRS)).filter(|max_dirs| *max_dirs != 0),
+    );
+
+    let mut projects = if args.no_cache {
+        scan(&config, &scan_opts, &scan_budget, args.strict)?.projects
+    } else {
+        let cache_file_path = cache_path("projects")?;
+        let cache_key = cache_key(&config.src_dirs, &scan_opts);
+
+        let cached_projects = if args.rebuild_cache {
+            None
+        } else {
+            read_cache(&cache_file_path, cache_key)
+        };
+
+        match cached_projects {
+            Some(projects) => {
+                status::debug(&format!(
+                    "Read {} projects from the cache '{}'",
+                    projects.len(),
+                    cache_file_path.display()
+                ));
+                projects
+            }
+            None if args.list_cached => {
+                status::debug("The project cache is missing or out of date, so listing nothing");
+                return Ok(());
+            }
+            None => {
+                status::debug("The project cache is missing or out of date, so scanning");
+                let scan = scan(&config, &scan_opts, &scan_budget, args.strict)?;
+                // Skipped directories aren't tracked by the cache, so it would miss them once
+                // they can be read again
+                if scan.errors.is_empty() && !scan.truncated {
+                    if let Err(e) = write_cache(&cache_file_path, cache_key, &scan) {
+                        eprintln!("Failed to write the project cache: {}", e);
+                    }
+                }
+                scan.projects
+            }
+        }
+    };
+
+    // Listed projects aren't cached since finding them doesn't need a scan
+    let found = projects
         .iter()
-        .filter_map(|src_dir| {
-            let dir = fs::read_dir(&src_dir.path).ok()?;
-            Some(get_projects(dir, src_dir.search_depth).ok()?)
-        })
-        .flatten()
-        .collect::<Vec<_>>();
+        .map(|project| canonical_path(project.full_path()))
+        .collect::<HashSet<_>>();
+    for path in &config.projects {
+        if !found.contains(&canonical_path(path)) {
+            projects.push(Project::new(path.clone()));
+        }
+    }
 
-    let list_sessions = Command::new(TMUX_BIN)
-        .arg("list-sessions")
-        .stdout(Stdio::piped())
-        .spawn()?;
+    sort_projects(&mut projects, args.sort.or(config.sort).unwrap_or_default());
+    // Ranking by zoxide is a nicety, so it falls back to the history without a word if zoxide
+    // isn't installed
+    let zoxide_scores = if config.zoxide {
+        resolve_binary(ZOXIDE_BIN, None)
+            .ok()
+            .and_then(|zoxide_bin| query_scores(&zoxide_bin))
+    } else {
+        None
+    };
+    match zoxide_scores {
+        Some(scores) => rank_projects(&mut projects, &scores),
+        None => rank_projects(&mut projects, &read_history(&cache_path(HISTORY_FILE)?)),
+    }
+
+    if args.list {
+        let mut stdout = io::stdout().lock();
+        match args.format {
+            ListFormat::Paths => {
+                for project in &projects {
+                    stdout.write_all(project.full_path().as_os_str().as_encoded_bytes())?;
+                    stdout.write_all(b"\n")?;
+                }
+            }
+            ListFormat::Tsv => {
+                for project in &projects {
+                    let path = project.full_path();
+                    if let Some(name) = path.file_name() {
+                        stdout.write_all(name.as_encoded_bytes())?;
+                    }
+                    stdout.write_all(b"\t")?;
+                    stdout.write_all(path.as_os_str().as_encoded_bytes())?;
+                    stdout.write_all(b"\n")?;
+                }
+            }
+            ListFormat::Json => {
+                let listed = projects
+                    .iter()
+                    .map(|project| ListedProject {
+                        name: project.name(),
+                        path: project.full_path().to_string_lossy(),
+                        source: project.src_dir().map(Path::to_string_lossy),
+                    })
+                    .collect::<Vec<_>>();
+
+                // Pretty printed for people, compact for other programs
+                if stdout.is_terminal() {
+                    serde_json::to_writer_pretty(&mut stdout, &listed)?;
+                } else {
+                    serde_json::to_writer(&mut stdout, &listed)?;
+                }
+                stdout.write_all(b"\n")?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    let replacement = config
+        .session_name_replacement
+        .unwrap_or(DEFAULT_SESSION_NAME_REPLACEMENT);
+
+    if let (Some(Subcommand::Clean { dry_run, all }), Some(multiplexer)) =
+        (args.command, &multiplexer)
+    {
+        return clean_sessions(
+            multiplexer.as_ref(),
+            &config,
+            &projects,
+            replacement,
+            dry_run,
+            all,
+        );
+    }
 
-    let list_sessions_output = list_sessions.wait_with_output()?;
+    if let Some(name) = &args.name {
+        let selected_project = find_project_by_name(&projects, name, args.match_case)?;
+        remember_opened(selected_project, args.dry_run);
 
-    let active_sessions = match list_sessions_output.status.code() {
-        Some(0) => String::from_utf8_lossy(&list_sessions_output.stdout),
-        Some(1) => Cow::from(""), // Error code 1 means there are no active tmux sessions
-        Some(code) => return Err(anyhow!("tmux errored with code: {}.", code)),
-        None => return Err(anyhow!("Nothing was returned by tmux.")),
+        if args.edit {
+            return open_editor(selected_project, multiplexer.as_deref());
+        }
+        return match &multiplexer {
+            Some(multiplexer) => switch_to_project(
+                multiplexer.as_ref(),
+                &mut multiplexer.list_sessions()?.into_iter().collect(),
+                selected_project,
+                session_setup(&config, selected_project, replacement)?,
+                args.detach,
+                args.attach_only,
+            ),
+            None => open_shell(selected_project),
+        };
+    }
+
+    let Some(fzf_bin) = fzf_bin else {
+        unreachable!("fzf is resolved unless the project is selected by name");
     };
+    // Listed in the order the multiplexer gives, and looked up in the set when switching
+    let listed_sessions = match &multiplexer {
+        Some(multiplexer) if !args.edit => multiplexer.list_sessions()?,
+        _ => Vec::new(),
+    };
+    let mut active_sessions = listed_sessions.iter().cloned().collect::<HashSet<_>>();
+    // Prefixes active sessions in the fzf list to distinguish them from project directories
+    let session_marker = multiplexer
+        .as_ref()
+        .map_or_else(String::new, |multiplexer| {
+            format!("[{}] ", multiplexer.name())
+        });
 
-    // TODO: Add active sessions into fzf list
-    let active_sessions = active_sessions
-        .lines()
-        .filter_map(|line| line.split_once(':'))
-        .map(|(session_name, _)| session_name)
-        .collect::<Vec<_>>();
+    let mut fzf = Command::new(&fzf_bin);
+    if args.multi {
+        fzf.arg("--multi");
+    }
+    if let Some(query) = &args.query {
+        fzf.arg("--query").arg(query);
+    }
+    if args.select_1 {
+        fzf.arg("--select-1");
+    }
+    if args.exit_0 {
+        fzf.arg("--exit-0");
+    }
+    if let Some(prompt) = args.prompt.as_ref().or(config.prompt.as_ref()) {
+        fzf.arg("--prompt").arg(prompt);
+    }
+    if let Some(header) = args.header.as_ref().or(config.header.as_ref()) {
+        fzf.arg("--header")
+            .arg(header.replace("{count}", &projects.len().to_string()));
+    }
+    let show_source = args.show_source || config.show_source;
+    // Sessions are only listed when a project can be switched to
+    let active_marker = if multiplexer.is_some() && !args.edit {
+        config
+            .active_marker
+            .as_deref()
+            .unwrap_or(DEFAULT_ACTIVE_MARKER)
+    } else {
+        ""
+    };
+    // The marker is a field before the path, padded for the projects without a session so that
+    // the paths line up
+    let path_field = usize::from(!active_marker.is_empty());
+    let marker_padding = " ".repeat(active_marker.chars().count());
+    // fzf draws on the terminal even when its output is piped, so only `$NO_COLOR` matters
+    let color = status::color_allowed();
+    if !color {
+        fzf.arg("--color=bw");
+    }
+    if show_source || path_field > 0 {
+        // The source directory is a dimmed field after the path
+        fzf.arg("--delimiter").arg("\t");
+        if color {
+            fzf.arg("--ansi");
+        }
+    }
+    if path_field > 0 {
+        // The tab after the marker is shown as a single space, and the marker isn't matched
+        fzf.arg("--tabstop=1").arg("--nth").arg("2..");
+    }
+    if !config.no_preview {
+        let path = if show_source || path_field > 0 {
+            format!("{{{}}}", path_field + 1)
+        } else {
+            "{}".to_string()
+        };
+        fzf.arg("--preview").arg(preview_command(&path));
+    }
 
-    let mut fzf = Command::new(FZF_BIN)
+    let mut fzf = fzf
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
-        .expect("Failed to run `fzf`.");
+        .map_err(|e| anyhow!("Failed to run `{}`: {}", fzf_bin.display(), e))?;
+
+    if args.group {
+        // Stable, so projects keep their order within each source directory
+        projects.sort_by_key(|project| {
+            config
+                .src_dirs
+                .iter()
+                .position(|src_dir| Some(src_dir.path.as_path()) == project.src_dir())
+        });
+    }
+
+    // Sessions named after a listed project are reached by selecting the project instead
+    let project_sessions = project_session_names(&config, &projects, replacement);
 
     if let Some(mut stdin) = fzf.stdin.take() {
-        let project_strs = projects.iter().filter_map(|p| p.full_path().to_str()).fold(
-            String::new(),
-            |mut acc, path| {
-                acc.push_str(path);
-                acc.push('\n');
+        let push_marker = |acc: &mut Vec<u8>, active: bool| {
+            if path_field == 0 {
+                return;
+            }
+            if !active {
+                acc.extend_from_slice(marker_padding.as_bytes());
+            } else if color {
+                acc.extend_from_slice(format!("\x1b[32m{}\x1b[0m", active_marker).as_bytes());
+            } else {
+                acc.extend_from_slice(active_marker.as_bytes());
+            }
+            acc.push(b'\t');
+        };
+
+        // Written as bytes so that projects with paths that aren't UTF-8 are listed too
+        let mut group = None;
+        let project_strs = projects.iter().fold(Vec::new(), |mut acc, p| {
+            if args.group && group != Some(p.src_dir()) {
+                group = Some(p.src_dir());
+                push_marker(&mut acc, false);
+                acc.extend_from_slice(GROUP_HEADER_PREFIX.as_bytes());
+                match p.src_dir() {
+                    Some(src_dir) => acc.extend_from_slice(src_dir.as_os_str().as_encoded_bytes()),
+                    None => acc.extend_from_slice(EXPLICIT_SOURCE.as_bytes()),
+                }
+                acc.push(b'\n');
+            }
+            let active = path_field > 0
+                && project_session_name(&config, p, replacement)
+                    .is_some_and(|session| active_sessions.contains(&session));
+            push_marker(&mut acc, active);
+            acc.extend_from_slice(p.full_path().as_os_str().as_encoded_bytes());
+            if show_source {
+                acc.push(b'\t');
+                if color {
+                    acc.extend_from_slice(b"\x1b[2m");
+                }
+                match p.src_dir() {
+                    Some(src_dir) => acc.extend_from_slice(src_dir.as_os_str().as_encoded_bytes()),
+                    None => acc.extend_from_slice(EXPLICIT_SOURCE.as_bytes()),
+                }
+                if color {
+                    acc.extend_from_slice(b"\x1b[0m");
+                }
+            }
+            acc.push(b'\n');
+            acc
+        });
+
+        let session_strs = listed_sessions
+            .iter()
+            .filter(|session| !project_sessions.contains(*session))
+            .fold(Vec::new(), |mut acc, session| {
+                push_marker(&mut acc, false);
+                acc.extend_from_slice(session_marker.as_bytes());
+                acc.extend_from_slice(session.as_bytes());
+                acc.push(b'\n');
                 acc
-            },
+            });
+
+        let written = stdin
+            .write_all(&project_strs)
+            .and_then(|()| stdin.write_all(&session_strs));
+        match written {
+            // fzf stops reading once `--select-1` or `--exit-0` apply, which isn't an error
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                return Err(anyhow!("Failed to write the projects to `fzf`: {}", e));
+            }
+            _ => {}
+        }
+    }
+
+    let fzf_output = fzf
+        .wait_with_output()
+        .map_err(|e| anyhow!("Failed to wait for `fzf`: {}", e))?;
+
+    let selections = match fzf_output.status.code() {
+        Some(0) => fzf_output
+            .stdout
+            .split(|&byte| byte == b'\n')
+            // Only the path is kept of the lines showing the marker or the source directory
+            .map(|line| {
+                line.split(|&byte| byte == b'\t')
+                    .nth(path_field)
+                    .unwrap_or(line)
+            })
+            .map(<[u8]>::trim_ascii)
+            .filter(|selection| !selection.is_empty())
+            .collect::<Vec<_>>(),
+        Some(1) => return Err(Failure::NoMatch.into()),
+        Some(130) => return Err(Failure::Aborted.into()),
+        Some(code) => return Err(anyhow!("fzf errored with code: {}.", code)),
+        None => return Err(anyhow!("Nothing was returned by fzf.")),
+    };
+
+    if selections.is_empty() {
+        return Err(Failure::Aborted.into());
+    }
+
+    // Selecting a group header does nothing
+    let selections = selections
+        .into_iter()
+        .filter(|selection| !selection.starts_with(GROUP_HEADER_PREFIX.as_bytes()))
+        .collect::<Vec<_>>();
+    let Some((last_selection, other_selections)) = selections.split_last() else {
+        return Ok(());
+    };
+
+    if args.edit {
+        let selected_project = selected_project(&projects, last_selection);
+        remember_opened(&selected_project, args.dry_run);
+        return open_editor(&selected_project, multiplexer.as_deref());
+    }
+
+    let Some(multiplexer) = multiplexer else {
+        let selected_project = selected_project(&projects, last_selection);
+        remember_opened(&selected_project, args.dry_run);
+        return open_shell(&selected_project);
+    };
+
+    // Only the last selection is switched to, the others are started in the background
+    for selection in other_selections {
+        if let Some(session_name) = selection.strip_prefix(session_marker.as_bytes()) {
+            if args.detach {
+                println!("{}", String::from_utf8_lossy(session_name));
+            }
+            continue;
+        }
+
+        let project = selected_project(&projects, selection);
+        remember_opened(&project, args.dry_run);
+        let setup = session_setup(&config, &project, replacement)?;
+        create_project_session(
+            multiplexer.as_ref(),
+            &mut active_sessions,
+            &project,
+            &setup,
+            args.attach_only,
+        )?;
+        if args.detach {
+            println!("{}", setup.session_name);
+        }
+    }
+
+    if let Some(session_name) = last_selection.strip_prefix(session_marker.as_bytes()) {
+        return switch_to_session(
+            multiplexer.as_ref(),
+            &String::from_utf8_lossy(session_name),
+            config.window.as_deref(),
+            args.detach,
         );
+    }
+
+    let selected_project = selected_project(&projects, last_selection);
+    remember_opened(&selected_project, args.dry_run);
+
+    let setup = session_setup(&config, &selected_project, replacement)?;
+
+    switch_to_project(
+        multiplexer.as_ref(),
+        &mut active_sessions,
+        &selected_project,
+        setup,
+        args.detach,
+        args.attach_only,
+    )
+}
+
+/// Returns the configured multiplexer, or the one the user is in if none is configured.
+///
+/// With `dry_run`, the commands that would change sessions are printed instead of run.
+fn open_multiplexer(config: &Config, dry_run: bool) -> Result<Box<dyn Multiplexer>> {
+    let kind = config.multiplexer.unwrap_or_else(MultiplexerKind::detect);
+    let multiplexer: Box<dyn Multiplexer> = match kind {
+        MultiplexerKind::Tmux => Box::new(Tmux::new(
+            resolve_binary(TMUX_BIN, config.tmux.as_deref())?,
+            config.tmux_server.clone(),
+            config.tmux_retries.unwrap_or(DEFAULT_TMUX_RETRIES),
+            dry_run,
+        )),
+        MultiplexerKind::Zellij => Box::new(Zellij::new(
+            resolve_binary(ZELLIJ_BIN, config.zellij.as_deref())?,
+            dry_run,
+        )),
+    };
+
+    let reason = match (config.multiplexer, kind) {
+        (Some(_), _) => "as configured",
+        (None, MultiplexerKind::Zellij) => "as $ZELLIJ is set and $TMUX isn't",
+        (None, MultiplexerKind::Tmux) => "by default",
+    };
+    status::debug(&format!("Using {} {}", multiplexer.name(), reason));
+
+    Ok(multiplexer)
+}
+
+/// Kills the active sessions that aren't named after one of the `projects`, after asking for
+/// confirmation.
+///
+/// Unless `all` is set, only sessions started in a directory that no longer exists are killed, so
+/// that sessions that weren't started for a project are kept.
+fn clean_sessions(
+    multiplexer: &dyn Multiplexer,
+    config: &Config,
+    projects: &[Project],
+    replacement: char,
+    dry_run: bool,
+    all: bool,
+) -> Result<()> {
+    let project_sessions = project_session_names(config, projects, replacement);
+
+    let mut orphans = Vec::new();
+    for session in multiplexer.list_sessions()? {
+        if project_sessions.contains(&session) {
+            continue;
+        }
+
+        if all
+            || multiplexer
+                .session_dir(&session)?
+                .is_some_and(|dir| !dir.exists())
+        {
+            orphans.push(session);
+        }
+    }
 
-        stdin
-            .write_all(project_strs.as_bytes())
-            .expect("Failed to write to `fzf` stdin");
+    if orphans.is_empty() {
+        status::print("No sessions to kill.");
+        return Ok(());
     }
 
-    let fzf_output = fzf.wait_with_output().unwrap();
+    status::print("Sessions to kill:");
+    for session in &orphans {
+        status::print(&format!("  {}", session));
+    }
 
-    let selected_project_path = match fzf_output.status.code() {
-        Some(0) => {
-            let project_path = OsStr::from_bytes(fzf_output.stdout.as_slice());
-            let project_path = project_path
-                .to_str()
-                .map(|s| s.trim())
-                .ok_or_else(|| anyhow!("Failed to convert path from OsStr to str"))?;
-            let project_path = PathBuf::from(project_path);
+    if dry_run || !confirm("Kill these sessions?")? {
+        return Ok(());
+    }
 
-            project_path
+    for session in &orphans {
+        multiplexer.kill_session(session)?;
+        status::print(&format!("Killed session '{}'", session));
+    }
+
+    Ok(())
+}
+
+/// Returns the config files to read, with the config shared by every user first so that the
+/// user's `config_file_path` takes precedence.
+fn config_file_paths(config_file_path: &Path) -> Vec<PathBuf> {
+    system_config_dir()
+        .map(|dir| find_config_file(&dir))
+        .into_iter()
+        .chain([config_file_path.to_path_buf()])
+        .collect()
+}
+
+/// Prints the version, the config files and binaries that would be used and the environment
+/// variables that change them, reporting problems instead of failing on them.
+fn doctor(config_file_paths: &[PathBuf]) -> Result<()> {
+    println!("{} {}", PROJECT_NAME, env!("CARGO_PKG_VERSION"));
+
+    for path in config_file_paths {
+        let state = if !path.exists() {
+            "does not exist".to_string()
+        } else {
+            match fs::read(path) {
+                Ok(_) => "readable".to_string(),
+                Err(e) => format!("not readable: {}", e),
+            }
+        };
+        println!("config: {} ({})", path.display(), state);
+    }
+    let config = match read_config_files(config_file_paths, &home_dir()?) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("config error: {:#}", e);
+            Config::default()
+        }
+    };
+
+    let binaries = [
+        (FZF_BIN, config.fzf.as_deref()),
+        (TMUX_BIN, config.tmux.as_deref()),
+        (ZELLIJ_BIN, config.zellij.as_deref()),
+        (ZOXIDE_BIN, None),
+    ];
+    for (name, configured) in binaries {
+        match resolve_binary(name, configured) {
+            Ok(path) => println!("{}: {}", name, path.display()),
+            Err(e) => println!("{}: {}", name, e),
+        }
+    }
+    let multiplexer = match config.multiplexer {
+        Some(kind) => format!("{:?} (configured)", kind),
+        None => format!("{:?} (detected)", MultiplexerKind::detect()),
+    };
+    println!("multiplexer: {}", multiplexer.to_lowercase());
+    println!("cache: {}", cache_path("projects")?.display());
+
+    for name in [
+        "TMUX",
+        "ZELLIJ",
+        "XDG_CONFIG_HOME",
+        "XDG_CACHE_HOME",
+        DEFAULT_SRC_DIR_ENV_VAR,
+        "SHELL",
+        "EDITOR",
+    ] {
+        match env::var_os(name) {
+            Some(value) => println!("${}: {}", name, value.to_string_lossy()),
+            None => println!("${}: unset", name),
         }
-        Some(130) => return Err(anyhow!("You did not select project.")),
+    }
+
+    Ok(())
+}
+
+/// Kills the active session selected with fzf, after asking unless `yes`.
+///
+/// Killing the session the user is in detaches them from it, or switches them to another one
+/// depending on tmux's `detach-on-destroy` option, so they're told before it's killed.
+fn kill_session(multiplexer: &dyn Multiplexer, fzf_bin: &Path, yes: bool) -> Result<()> {
+    let sessions = multiplexer.list_sessions()?;
+    if sessions.is_empty() {
+        status::print("No sessions to kill.");
+        return Ok(());
+    }
+
+    let mut fzf = Command::new(fzf_bin);
+    fzf.arg("--prompt").arg("kill> ");
+    if !status::color_allowed() {
+        fzf.arg("--color=bw");
+    }
+    let mut fzf = fzf
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run `{}`: {}", fzf_bin.display(), e))?;
+    if let Some(mut stdin) = fzf.stdin.take() {
+        let written = stdin.write_all(format!("{}\n", sessions.join("\n")).as_bytes());
+        match written {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                return Err(anyhow!("Failed to write the sessions to `fzf`: {}", e));
+            }
+            _ => {}
+        }
+    }
+
+    let fzf_output = fzf
+        .wait_with_output()
+        .map_err(|e| anyhow!("Failed to wait for `fzf`: {}", e))?;
+    let session = match fzf_output.status.code() {
+        Some(0) => String::from_utf8_lossy(&fzf_output.stdout)
+            .trim()
+            .to_string(),
+        Some(1) => return Err(Failure::NoMatch.into()),
+        Some(130) => return Err(Failure::Aborted.into()),
         Some(code) => return Err(anyhow!("fzf errored with code: {}.", code)),
         None => return Err(anyhow!("Nothing was returned by fzf.")),
     };
+    if session.is_empty() {
+        return Err(Failure::Aborted.into());
+    }
 
-    let selected_project = Project {
-        inner: selected_project_path,
+    let current = multiplexer.current_session()?.as_deref() == Some(session.as_str());
+    let question = if current {
+        format!(
+            "Kill session '{}'? You're in it, so {} will leave it.",
+            session,
+            multiplexer.name()
+        )
+    } else {
+        format!("Kill session '{}'?", session)
     };
+    if !yes && !confirm(&question)? {
+        return Ok(());
+    }
+
+    // Printed first, since this process may not outlive the session it's running in
+    status::print(&format!("Killing session '{}'", session));
+    multiplexer.kill_session(&session)
+}
 
-    switch_to_project_in_tmux(&selected_project, &active_sessions)
+/// Asks the user a yes or no `question`, defaulting to no.
+fn confirm(question: &str) -> Result<bool> {
+    let answer = ask(&format!("{} [y/N]", question))?;
+
+    Ok(matches!(answer.as_str(), "y" | "Y" | "yes"))
+}
+
+/// Asks the user `question` and returns the trimmed answer, which is empty at the end of stdin.
+fn ask(question: &str) -> Result<String> {
+    // On stderr like the status messages, but printed even when quiet since it needs an answer
+    eprint!("{} ", question);
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(answer.trim().to_string())
 }
 
-fn read_config_file<P: AsRef<Path>>(path: P) -> Result<Vec<SrcDir>> {
-    let src_dirs = fs::read_to_string(path.as_ref())?
-        .lines()
-        .filter_map(|line| line.split_once(' '))
-        .filter_map(|(path, depth)| {
-            let path = PathBuf::from_str(path).ok()?;
-            let search_depth = depth.parse::<u8>().ok()?;
-            Some(SrcDir { path, search_depth })
+/// Creates the config at `path` on the first run, asking the user whether to search `~/src` or
+/// which source directories to search instead.
+///
+/// Writes the default config if no source directory is given.
+fn set_up_config(path: &Path) -> Result<()> {
+    eprintln!("There is no config file at '{}' yet.", path.display());
+    let question = match env::var_os(DEFAULT_SRC_DIR_ENV_VAR) {
+        None => Some("Search for projects two directories deep in ~/src? [Y/n]".to_string()),
+        Some(value) if value.is_empty() => None,
+        Some(value) => Some(format!(
+            "Search for projects in '{}' from ${}? [Y/n]",
+            value.to_string_lossy(),
+            DEFAULT_SRC_DIR_ENV_VAR
+        )),
+    };
+    if let Some(question) = question {
+        let answer = ask(&question)?;
+        if !matches!(answer.as_str(), "n" | "N" | "no") {
+            return create_default_config(path);
+        }
+    }
+
+    create_commented_config(path)?;
+    let home = home_dir()?;
+    let mut added = false;
+    loop {
+        let dir = ask("Directory holding projects, or nothing to finish:")?;
+        if dir.is_empty() {
+            break;
+        }
+
+        let depth = ask(&format!(
+            "Number of directories between it and the projects [{}]:",
+            DEFAULT_DEPTH
+        ))?;
+        let depth = if depth.is_empty() {
+            DEFAULT_DEPTH
+        } else if let Ok(depth) = depth.parse() {
+            depth
+        } else {
+            eprintln!("'{}' is not a number of directories.", depth);
+            continue;
+        };
+
+        // Mistakes like a directory that doesn't exist are asked again instead of failing
+        match add_src_dir(path, &dir, depth, &home) {
+            Ok(_) => added = true,
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    if !added {
+        create_default_config(path)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the project in the directory named `name`, failing if there isn't exactly one.
+///
+/// If no project has that name, `name` is matched against the end of the project paths
+/// instead, so that `team-a/api` picks the `api` project in a `team-a` directory.
+fn find_project_by_name<'a>(
+    projects: &'a [Project],
+    name: &str,
+    match_case: MatchCase,
+) -> Result<&'a Project> {
+    let mut matches = projects
+        .iter()
+        .filter(|project| {
+            project
+                .name()
+                .is_some_and(|candidate| match_case.matches(name, candidate))
         })
         .collect::<Vec<_>>();
 
-    Ok(src_dirs)
+    let suffix = name.trim_matches('/');
+    if matches.is_empty() && suffix.contains('/') {
+        matches = projects
+            .iter()
+            .filter(|project| {
+                path_suffix(project.full_path(), suffix.split('/').count())
+                    .is_some_and(|candidate| match_case.matches(suffix, &candidate))
+            })
+            .collect();
+    }
+
+    match matches.as_slice() {
+        [] => Err(anyhow!("No project is named '{}'.", name)),
+        [project] => Ok(project),
+        _ => Err(anyhow!(
+            "Multiple projects are named '{}':\n{}",
+            name,
+            matches
+                .iter()
+                .map(|project| format!("  {}", project.full_path().display()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )),
+    }
 }
 
-fn get_projects(mut src_dir: ReadDir, depth: u8) -> Result<Vec<Project>> {
-    fn get_projects_recur(dir: &mut ReadDir, depth: u8, res: &mut Vec<Project>) -> Result<()> {
-        if depth > 1 {
-            while let Some(Ok(entry)) = dir.next() {
-                if let Ok(ref mut dir) = fs::read_dir(entry.path()) {
-                    get_projects_recur(dir, depth - 1, res)?;
-                }
+/// Returns the last `count` components of `path` joined with `/`, or `None` if it has fewer.
+fn path_suffix(path: &Path, count: usize) -> Option<String> {
+    let components = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let start = components.len().checked_sub(count)?;
+    Some(components[start..].join("/"))
+}
+
+/// Replaces this process with `$SHELL`, falling back to [`DEFAULT_SHELL`], started in `project`.
+fn open_shell(project: &Project) -> Result<()> {
+    let shell = env::var_os("SHELL")
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| OsString::from(DEFAULT_SHELL));
+
+    status::print(&format!("Entering '{}'", project.full_path().display()));
+
+    exec_in(project, Command::new(shell))
+}
+
+/// Opens `project` in `$EDITOR`, falling back to [`DEFAULT_EDITOR`], in a new window of the
+/// current session of `window_multiplexer` if given and in place of this process otherwise.
+fn open_editor(project: &Project, window_multiplexer: Option<&dyn Multiplexer>) -> Result<()> {
+    let editor = match env::var("EDITOR") {
+        Ok(editor) if !editor.trim().is_empty() => editor,
+        _ => find_in_path(DEFAULT_EDITOR)
+            .map(|_| DEFAULT_EDITOR.to_string())
+            .ok_or_else(|| {
+                anyhow!(
+                    "`$EDITOR` is not set and `{}` could not be found in $PATH.",
+                    DEFAULT_EDITOR
+                )
+            })?,
+    };
+
+    status::print(&format!("Editing '{}'", project.full_path().display()));
+
+    if let Some(multiplexer) = window_multiplexer {
+        return multiplexer.open_window(project.full_path(), &format!("{} .", editor));
+    }
+
+    // `$EDITOR` can contain arguments, like `code --wait`
+    let mut words = editor.split_whitespace();
+    let mut command = Command::new(words.next().unwrap_or(DEFAULT_EDITOR));
+    command.args(words).arg(".");
+
+    exec_in(project, command)
+}
+
+/// Replaces this process with `command` started in `project`.
+///
+/// Processes can't be replaced outside of Unix, so the command is waited on instead.
+fn exec_in(project: &Project, mut command: Command) -> Result<()> {
+    command.current_dir(project.full_path());
+
+    #[cfg(unix)]
+    let error = command.exec();
+    #[cfg(not(unix))]
+    let error = match command.status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(error) => error,
+    };
+
+    Err(anyhow!(
+        "Failed to start `{}` in '{}': {}",
+        command.get_program().to_string_lossy(),
+        project.full_path().display(),
+        error
+    ))
+}
+
+/// Records that `project` was opened in the history, only warning if it can't be written.
+///
+/// With `dry_run`, the history is left as it is so that the next run orders the projects the same.
+fn remember_opened(project: &Project, dry_run: bool) {
+    status::debug(&format!(
+        "Selected '{}' at '{}'",
+        project.name().unwrap_or_default(),
+        project.full_path().display()
+    ));
+    if dry_run {
+        status::debug("Not recording the project in the history with --dry-run");
+        return;
+    }
+    let recorded =
+        cache_path(HISTORY_FILE).and_then(|path| record_opened(&path, project.full_path()));
+    if let Err(e) = recorded {
+        eprintln!("Failed to update the history: {}", e);
+    }
+}
+
+/// Returns the listed project whose path was selected in fzf, or a new one if it isn't listed.
+fn selected_project(projects: &[Project], selection: &[u8]) -> Project {
+    let path = path_from_bytes(selection);
+
+    projects
+        .iter()
+        .find(|project| project.full_path() == path)
+        .cloned()
+        .unwrap_or_else(|| Project::new(path))
+}
+
+/// Returns the fzf preview command showing the git branch and status and the start of the README
+/// of the highlighted project, showing nothing for whatever isn't available.
+///
+/// `path` is the fzf placeholder for the path of the highlighted project.
+fn preview_command(path: &str) -> String {
+    format!(
+        "cd {} 2>/dev/null || exit 0; \
+        if git rev-parse --is-inside-work-tree >/dev/null 2>&1; then \
+            printf 'On branch %s\\n' \"$(git branch --show-current)\"; git status -s; echo; \
+        fi; \
+        for readme in README.md README README.rst README.txt readme.md; do \
+            if [ -f \"$readme\" ]; then head -n {} \"$readme\"; break; fi; \
+        done",
+        path, PREVIEW_README_LINES
+    )
+}
+
+/// Resolves the path to the `name` binary from the `FIND_PROJECT_<NAME>` environment variable,
+/// then the `configured` path, then `$PATH`.
+fn resolve_binary(name: &str, configured: Option<&Path>) -> Result<PathBuf> {
+    let env_var_name = format!("FIND_PROJECT_{}", name.to_uppercase());
+
+    let overridden = env::var_os(&env_var_name)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| configured.map(Path::to_path_buf));
+    if let Some(path) = overridden {
+        if !is_executable(&path) {
+            return Err(anyhow!(
+                "`{}` at '{}' is not an executable file.",
+                name,
+                path.display()
+            ));
+        }
+        return Ok(path);
+    }
+
+    find_in_path(name).ok_or_else(|| {
+        anyhow!(
+            "Could not find `{}` in $PATH. Install it, or set its path with the {} \
+            environment variable or the `{}` config key.",
+            name,
+            env_var_name,
+            name
+        )
+    })
+}
+
+/// Returns the first executable named `name` in `$PATH`.
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    env::var_os("PATH")
+        .iter()
+        .flat_map(env::split_paths)
+        .map(|dir| {
+            let mut path = dir.join(name);
+            // Names like `fzf-0.44` keep their extension, it's only added to a bare `fzf`
+            if cfg!(windows) && path.extension().is_none() {
+                path.set_extension(env::consts::EXE_EXTENSION);
             }
-            return Ok(());
+            path
+        })
+        .find(|path| is_executable(path))
+}
+
+/// Returns whether `path` is a file that can be executed.
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Returns whether `path` is a file that can be executed, which is any file outside of Unix.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Returns the canonical form of `path`, or `path` itself if it can't be canonicalized.
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Scans the configured source directories for projects, reporting what got in the way.
+fn scan(config: &Config, opts: &ScanOptions, budget: &ScanBudget, strict: bool) -> Result<Scan> {
+    warn_about_large_scans(config);
+    let scan = scan_projects(&config.src_dirs, opts, budget)?;
+    report_scan_errors(&scan, strict)?;
+
+    Ok(scan)
+}
+
+/// Warns about the source directories that look like they have too many directories to scan
+/// quickly, which doesn't stop them from being scanned.
+fn warn_about_large_scans(config: &Config) {
+    let threshold = config.scan_warning.unwrap_or(DEFAULT_SCAN_WARNING);
+    if threshold == 0 {
+        return;
+    }
+
+    for src_dir in &config.src_dirs {
+        let estimate = estimate_dirs(src_dir);
+        if estimate > threshold {
+            status::print(&format!(
+                "Scanning '{}' at a depth of {} may search around {} directories. Consider \
+                adding markers or lowering its depth.",
+                src_dir.path.display(),
+                src_dir.max_depth,
+                estimate
+            ));
         }
+    }
+}
 
-        while let Some(Ok(entry)) = dir.next() {
-            if let Ok(metadata) = entry.metadata() {
-                if metadata.is_dir() {
-                    res.push(Project {
-                        inner: entry.path(),
-                    });
-                }
+/// Prints the directories skipped while scanning to stderr, or fails on the first one if `strict`,
+/// and warns if scanning stopped early.
+fn report_scan_errors(scan: &Scan, strict: bool) -> Result<()> {
+    match scan.errors.first() {
+        Some(err) if strict => return Err(anyhow!("{}.", err)),
+        _ => {
+            for err in &scan.errors {
+                eprintln!("{}, skipping it.", err);
             }
         }
+    }
 
-        Ok(())
+    if scan.truncated {
+        eprintln!(
+            "Scanning took too long and was stopped, so some projects may be missing. Check the \
+            configured depths or raise `scan-timeout` and `max-dirs`."
+        );
     }
 
-    let mut projects = Vec::new();
-    get_projects_recur(&mut src_dir, depth, &mut projects)?;
+    Ok(())
+}
+
+/// Returns the source directory `project` was found in.
+fn src_dir_of<'a>(config: &'a Config, project: &Project) -> Option<&'a SrcDir> {
+    let path = project.src_dir()?;
 
-    Ok(projects)
+    config.src_dirs.iter().find(|src_dir| src_dir.path == path)
 }
 
-fn switch_to_project_in_tmux(project: &Project, active_sessions: &[&str]) -> Result<()> {
-    // Check if the user is currrently in a tmux session
-    let in_tmux = env::var("TMUX").is_ok();
-    let project_name = project
-        .name()
-        .ok_or_else(|| anyhow!("Failed to get project name."))?;
-    let session_exists = active_sessions.contains(&project_name);
+/// Returns the name of the session for `project`, from the session name template of the source
+/// directory it's in or else the default one.
+fn project_session_name(config: &Config, project: &Project, replacement: char) -> Option<String> {
+    let src_dir = src_dir_of(config, project);
+    let default_template = SessionNameTemplate::default();
+    let template = src_dir
+        .and_then(|src_dir| src_dir.session_name.as_ref())
+        .or(config.session_name.as_ref())
+        .unwrap_or(&default_template);
 
-    let mut switch_session = Command::new(TMUX_BIN);
+    project.session_name_from_template(
+        template,
+        src_dir.map(|src_dir| src_dir.path.as_path()),
+        replacement,
+    )
+}
+
+/// Returns the names of the sessions for `projects`, to compare the names of the active sessions
+/// against.
+fn project_session_names(
+    config: &Config,
+    projects: &[Project],
+    replacement: char,
+) -> HashSet<String> {
+    projects
+        .iter()
+        .filter_map(|project| project_session_name(config, project, replacement))
+        .collect()
+}
 
-    if in_tmux {
-        if session_exists {
-            println!("Switching to session '{}'", project_name);
+/// Returns how to name and set up a new session for `project`.
+///
+/// The startup command is the `startup` of the source directory it's in or else the command for
+/// the first marker it contains. The layout is the `layout` of the source directory it's in or else
+/// the default one.
+fn session_setup<'a>(
+    config: &'a Config,
+    project: &Project,
+    replacement: char,
+) -> Result<SessionSetup<'a>> {
+    let session_name = project_session_name(config, project, replacement).ok_or_else(|| {
+        anyhow!(
+            "Failed to name a session for '{}', its session name is empty.",
+            project.full_path().display()
+        )
+    })?;
+    let src_dir = src_dir_of(config, project);
 
-            // Command: "tmux switch -t {project.name}"
-            switch_session
-                .arg("switch") // switch session
-                .arg("-t") // target session name
-                .arg(project_name);
-        } else {
-            println!("Creating new session '{}'", project_name);
-
-            // Command: "tmux new -c {project.path} -s {project.name} -d"
-            let mut _create_session_as_daemon = Command::new(TMUX_BIN)
-                .arg("new-session") // create new session
-                .arg("-c") // change current working directory
-                .arg(
-                    project
-                        .full_path()
-                        .to_str()
-                        .ok_or_else(|| anyhow!("Failed to convert full path to str."))?,
-                )
-                .arg("-s") // new session name
-                .arg(project_name)
-                .arg("-d") // initialize session in the background
-                .spawn()?;
+    let startup = src_dir
+        .and_then(|src_dir| src_dir.startup.as_deref())
+        .or_else(|| {
+            config
+                .startup
+                .iter()
+                .find(|startup| project.full_path().join(&startup.marker).exists())
+                .map(|startup| startup.command.as_str())
+        });
+    let layout = src_dir
+        .and_then(|src_dir| src_dir.layout.as_ref())
+        .or(config.layout.as_ref())
+        .and_then(|layout| config.layouts.get(layout));
+
+    Ok(SessionSetup {
+        session_name,
+        startup,
+        layout,
+        window: config.window.as_deref(),
+    })
+}
+
+/// How to name and set up a new session, and where to land when switching to it.
+#[derive(Debug, Default, Clone)]
+struct SessionSetup<'a> {
+    session_name: String,
+    /// The command to run in the first window.
+    startup: Option<&'a str>,
+    layout: Option<&'a Layout>,
+    /// The name of the window to select when switching to the session, if it has one.
+    window: Option<&'a str>,
+}
+
+/// Switches to the session for `project`, creating it and setting it up if it isn't one of the
+/// `active_sessions`.
+///
+/// With `detach`, the name of the session is printed instead of switching to it, and with
+/// `attach_only`, it fails instead of creating the session.
+fn switch_to_project(
+    multiplexer: &dyn Multiplexer,
+    active_sessions: &mut HashSet<String>,
+    project: &Project,
+    setup: SessionSetup,
+    detach: bool,
+    attach_only: bool,
+) -> Result<()> {
+    create_project_session(multiplexer, active_sessions, project, &setup, attach_only)?;
+
+    switch_to_session(multiplexer, &setup.session_name, setup.window, detach)
+}
+
+/// Switches to the `session_name` session, selecting its window named `window` first if it has
+/// one.
+///
+/// With `detach`, the name of the session is printed instead of switching to it, so that the
+/// caller can attach to it however it likes.
+fn switch_to_session(
+    multiplexer: &dyn Multiplexer,
+    session_name: &str,
+    window: Option<&str>,
+    detach: bool,
+) -> Result<()> {
+    if let Some(window) = window {
+        multiplexer.select_window(session_name, window)?;
+    }
+
+    if detach {
+        println!("{}", session_name);
+        return Ok(());
+    }
+
+    multiplexer.attach_or_switch(session_name)
+}
+
+/// Creates a session in the background for `project` and sets it up, unless it is one of the
+/// `active_sessions`, which the new session is added to.
+///
+/// With `attach_only`, fails instead of creating the session.
+fn create_project_session(
+    multiplexer: &dyn Multiplexer,
+    active_sessions: &mut HashSet<String>,
+    project: &Project,
+    setup: &SessionSetup,
+    attach_only: bool,
+) -> Result<()> {
+    let session_name = setup.session_name.as_str();
+
+    if active_sessions.contains(session_name) {
+        status::debug(&format!(
+            "Session '{}' already exists, so it is reused",
+            session_name
+        ));
+        return Ok(());
+    }
+    if attach_only {
+        return Err(anyhow!(
+            "'{}' has no session named '{}', and --attach-only doesn't create one.",
+            project.full_path().display(),
+            session_name
+        ));
+    }
+
+    status::notice(&format!("Creating new session '{}'", session_name));
+    multiplexer.create_session(session_name, project.full_path())?;
+    active_sessions.insert(session_name.to_string());
 
-            // Command: "tmux attach -t {project.name}"
-            switch_session
-                .arg("switch") // switch session
-                .arg("-t") // target session name
-                .arg(project_name);
+    if let Some(layout) = setup.layout {
+        multiplexer.apply_layout(session_name, project.full_path(), layout)?;
+    }
+    if let Some(startup) = setup.startup {
+        multiplexer.send_command(session_name, startup)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    /// A multiplexer that keeps the names of the sessions it creates and is switched to.
+    #[derive(Default)]
+    struct FakeMultiplexer {
+        sessions: RefCell<Vec<String>>,
+        switched_to: RefCell<Vec<String>>,
+    }
+
+    impl Multiplexer for FakeMultiplexer {
+        fn name(&self) -> &'static str {
+            "fake"
         }
-    } else {
-        if session_exists {
-            println!("Attaching to session '{}'", project_name);
-
-            // Command: "tmux attach -t {project.name}"
-            switch_session
-                .arg("attach") // attach to session
-                .arg("-t") // target session name
-                .arg(project_name);
-        } else {
-            println!("Creating new session '{}'", project_name);
-
-            // Command: "tmux new -c {project.path} -s {project.name}"
-            switch_session
-                .arg("new-session") // create new session
-                .arg("-c") // change current working directory
-                .arg(
-                    project
-                        .full_path()
-                        .to_str()
-                        .ok_or_else(|| anyhow!("Failed to convert full path to str."))?,
-                )
-                .arg("-s") // new session name
-                .arg(project_name);
+
+        fn list_sessions(&self) -> Result<Vec<String>> {
+            Ok(self.sessions.borrow().clone())
+        }
+
+        fn create_session(&self, session_name: &str, _dir: &Path) -> Result<()> {
+            self.sessions.borrow_mut().push(session_name.to_string());
+            Ok(())
+        }
+
+        fn kill_session(&self, session_name: &str) -> Result<()> {
+            self.sessions
+                .borrow_mut()
+                .retain(|name| name != session_name);
+            Ok(())
+        }
+
+        fn send_command(&self, _session_name: &str, _command: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn attach_or_switch(&self, session_name: &str) -> Result<()> {
+            self.switched_to.borrow_mut().push(session_name.to_string());
+            Ok(())
         }
     }
 
-    switch_session.spawn()?;
+    /// Returns the long flags of `command` and its subcommands, except the hidden ones and the ones
+    /// clap adds.
+    fn long_flags(command: &clap::Command) -> Vec<String> {
+        command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .filter_map(clap::Arg::get_long)
+            .map(String::from)
+            .chain(command.get_subcommands().flat_map(long_flags))
+            .collect()
+    }
 
-    Ok(())
+    #[test]
+    fn completions_complete_every_long_flag() {
+        let flags = long_flags(&Args::command());
+        assert!(flags.iter().any(|flag| flag == "attach-only"));
+
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script =
+                completions::generate(shell, &mut Args::command(), "find_project").unwrap();
+            for flag in &flags {
+                let completed = match shell {
+                    Shell::Fish => format!("-l {}", flag),
+                    _ => format!("--{}", flag),
+                };
+                assert!(
+                    script.contains(&completed),
+                    "{:?} misses {}",
+                    shell,
+                    completed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn switches_to_the_session_it_created() {
+        // Keeps the messages about creating sessions out of the test output
+        status::init(true, false);
+        let config = Config::default();
+
+        for (name, session_name) in [("v1.2", "v1_2"), ("a:b", "a_b")] {
+            let multiplexer = FakeMultiplexer::default();
+            let project = Project::new(Path::new("/home/user/src").join(name));
+
+            // The second time, the session created the first time is found among the active ones
+            for _ in 0..2 {
+                let mut active_sessions =
+                    multiplexer.list_sessions().unwrap().into_iter().collect();
+                let setup = session_setup(&config, &project, '_').unwrap();
+                switch_to_project(
+                    &multiplexer,
+                    &mut active_sessions,
+                    &project,
+                    setup,
+                    false,
+                    false,
+                )
+                .unwrap();
+            }
+
+            assert_eq!(*multiplexer.sessions.borrow(), [session_name]);
+            assert_eq!(
+                *multiplexer.switched_to.borrow(),
+                [session_name, session_name]
+            );
+            assert!(multiplexer.session_exists(session_name).unwrap());
+            assert!(!multiplexer.session_exists(name).unwrap());
+        }
+    }
 }
@@ -0,0 +1,800 @@
+//! The terminal multiplexers sessions can be opened in.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+
+use crate::status;
+
+/// Replaces the characters tmux doesn't allow in session names by default.
+pub const DEFAULT_SESSION_NAME_REPLACEMENT: char = '_';
+
+/// Returns the tmux session name for the project named `project_name`, replacing the `.` and `:`
+/// characters tmux doesn't allow with `replacement`.
+pub fn session_name(project_name: &str, replacement: char) -> String {
+    project_name.replace(['.', ':'], &replacement.to_string())
+}
+
+/// The placeholders that can be used in a [`SessionNameTemplate`].
+pub const SESSION_NAME_PLACEHOLDERS: [&str; 3] = ["name", "parent", "source"];
+
+/// A session name containing placeholders for a project, like `{parent}-{name}`.
+///
+/// `{name}` is the name of the project directory, `{parent}` the name of the directory containing
+/// it and `{source}` the name of its source directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionNameTemplate(String);
+
+impl SessionNameTemplate {
+    /// Creates a template, failing if it contains an unknown or unterminated placeholder.
+    pub fn new(template: &str) -> Result<Self> {
+        let template = Self(template.to_string());
+        template.expand(|_| "")?;
+
+        Ok(template)
+    }
+
+    /// Replaces each placeholder in the template with the value returned by `value` for it.
+    pub fn expand<'a>(&self, mut value: impl FnMut(&str) -> &'a str) -> Result<String> {
+        let mut expanded = String::with_capacity(self.0.len());
+        let mut rest = self.0.as_str();
+
+        while let Some(start) = rest.find('{') {
+            expanded.push_str(&rest[..start]);
+
+            let Some(len) = rest[start..].find('}') else {
+                return Err(anyhow!(
+                    "Unterminated placeholder in session name template '{}'.",
+                    self.0
+                ));
+            };
+            let placeholder = &rest[start + 1..start + len];
+            if !SESSION_NAME_PLACEHOLDERS.contains(&placeholder) {
+                return Err(anyhow!(
+                    concat!(
+                        "Unknown placeholder '{{{}}}' in session name template '{}', ",
+                        "expected '{{name}}', '{{parent}}' or '{{source}}'."
+                    ),
+                    placeholder,
+                    self.0
+                ));
+            }
+
+            expanded.push_str(value(placeholder));
+            rest = &rest[start + len + 1..];
+        }
+        expanded.push_str(rest);
+
+        Ok(expanded)
+    }
+}
+
+impl Default for SessionNameTemplate {
+    fn default() -> Self {
+        Self("{name}".to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for SessionNameTemplate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let template = String::deserialize(deserializer)?;
+        Self::new(&template).map_err(de::Error::custom)
+    }
+}
+
+/// The terminal multiplexers that are supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiplexerKind {
+    Tmux,
+    Zellij,
+}
+
+impl MultiplexerKind {
+    /// Returns the multiplexer the user is currently in, defaulting to tmux.
+    pub fn detect() -> Self {
+        if env::var_os("ZELLIJ").is_some() && env::var_os("TMUX").is_none() {
+            MultiplexerKind::Zellij
+        } else {
+            MultiplexerKind::Tmux
+        }
+    }
+}
+
+/// The windows opened in a new session.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layout {
+    pub windows: Vec<LayoutWindow>,
+}
+
+/// A window of a [`Layout`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutWindow {
+    pub name: Option<String>,
+    /// The command run in the window, which is left with just a shell if unset.
+    pub command: Option<String>,
+    /// The commands run in panes split off the window, where an empty command leaves just a shell.
+    #[serde(default)]
+    pub panes: Vec<String>,
+}
+
+/// A terminal multiplexer that manages named sessions.
+pub trait Multiplexer {
+    /// The name of the multiplexer, e.g. `tmux`.
+    fn name(&self) -> &'static str;
+
+    /// Returns the names of the active sessions.
+    fn list_sessions(&self) -> Result<Vec<String>>;
+
+    /// Returns whether a session named `session_name` is active.
+    fn session_exists(&self, session_name: &str) -> Result<bool> {
+        Ok(self
+            .list_sessions()?
+            .iter()
+            .any(|session| session == session_name))
+    }
+
+    /// Returns the directory the `session_name` session was started in, if it's known.
+    fn session_dir(&self, _session_name: &str) -> Result<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    /// Returns the name of the session the user is currently in, if any.
+    fn current_session(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Creates the `session_name` session in the background, starting in `dir`.
+    fn create_session(&self, session_name: &str, dir: &Path) -> Result<()>;
+
+    /// Ends the `session_name` session.
+    fn kill_session(&self, session_name: &str) -> Result<()>;
+
+    /// Opens the windows of `layout` in the new `session_name` session, starting in `dir`.
+    fn apply_layout(&self, session_name: &str, _dir: &Path, _layout: &Layout) -> Result<()> {
+        Err(anyhow!(
+            "Can't apply a layout to session '{}', layouts are only supported by tmux.",
+            session_name
+        ))
+    }
+
+    /// Opens a window running `command` in `dir` in the session the user is currently in.
+    fn open_window(&self, _dir: &Path, _command: &str) -> Result<()> {
+        Err(anyhow!("Opening windows is only supported by tmux."))
+    }
+
+    /// Selects the window named `window_name` in the `session_name` session if it has one, leaving
+    /// the session as it is otherwise. Only tmux names windows, so others do nothing.
+    fn select_window(&self, _session_name: &str, _window_name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Types `command` into the `session_name` session and runs it.
+    fn send_command(&self, session_name: &str, command: &str) -> Result<()>;
+
+    /// Switches to the `session_name` session, attaching to it if not already in the multiplexer.
+    fn attach_or_switch(&self, session_name: &str) -> Result<()>;
+}
+
+/// A multiplexer command that exited unsuccessfully, which is exited with the same code.
+#[derive(Debug)]
+pub struct CommandFailed {
+    /// The name of the multiplexer binary, e.g. `tmux`.
+    pub bin: &'static str,
+    /// What the command was doing, like `creating the session`.
+    pub doing: String,
+    /// The code the command exited with, which is `None` if it was terminated by a signal.
+    pub code: Option<i32>,
+}
+
+impl fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{} failed {} with code: {}.", self.bin, self.doing, code),
+            None => write!(f, "{} was terminated while {}.", self.bin, self.doing),
+        }
+    }
+}
+
+impl std::error::Error for CommandFailed {}
+
+/// Turns an unsuccessful exit `status` of `bin` into a [`CommandFailed`] error describing what
+/// it was doing.
+fn check_status(bin: &'static str, status: ExitStatus, doing: &str) -> Result<()> {
+    if status.success() {
+        return Ok(());
+    }
+
+    Err(CommandFailed {
+        bin,
+        doing: doing.to_string(),
+        code: status.code(),
+    }
+    .into())
+}
+
+/// Runs `command` and waits for it to exit, or only prints it if `dry_run`, in which case it
+/// counts as successful.
+fn run_command(command: &mut Command, dry_run: bool) -> Result<ExitStatus> {
+    if dry_run {
+        println!("{}", format_command(command));
+        return Ok(ExitStatus::default());
+    }
+
+    Ok(command.status()?)
+}
+
+/// Formats `command` the way it would be typed into a shell, quoting the arguments that need it.
+fn format_command(command: &Command) -> String {
+    let quote = |arg: &OsStr| {
+        let arg = arg.to_string_lossy();
+        if !arg.is_empty()
+            && arg
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_./:=@^#{}".contains(c))
+        {
+            arg.into_owned()
+        } else {
+            format!("'{}'", arg.replace('\'', "'\\''"))
+        }
+    };
+
+    let mut formatted = String::new();
+    if let Some(dir) = command.get_current_dir() {
+        formatted.push_str(&format!("cd {} && ", quote(dir.as_os_str())));
+    }
+    formatted.push_str(&quote(command.get_program()));
+    for arg in command.get_args() {
+        formatted.push(' ');
+        formatted.push_str(&quote(arg));
+    }
+
+    formatted
+}
+
+/// The errors `tmux list-sessions` fails with when there are no active sessions.
+const NO_SESSIONS_ERRORS: [&str; 3] = ["no server running", "no sessions", "error connecting to"];
+
+/// The errors tmux can fail with right after the server was started or a session was created,
+/// which go away when the command is run again.
+const TRANSIENT_ERRORS: [&str; 3] = [
+    "can't find session",
+    "no server running",
+    "error connecting to",
+];
+
+/// How many times switching to or attaching to a tmux session is retried by default.
+pub const DEFAULT_TMUX_RETRIES: u32 = 3;
+
+/// How long to wait before the first retry, doubling with each one after it.
+const RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// A tmux server other than the default one, like the ones started with `tmux -L <name>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TmuxServer {
+    /// The name of the server's socket in tmux's socket directory, as passed to `-L`.
+    Name(String),
+    /// The path to the server's socket, as passed to `-S`.
+    Socket(PathBuf),
+}
+
+#[derive(Debug)]
+pub struct Tmux {
+    bin: PathBuf,
+    /// The server every command is sent to, or the default one if unset.
+    server: Option<TmuxServer>,
+    /// How many times switching to a session is retried if it fails with a transient error.
+    retries: u32,
+    /// Whether to print the commands that change sessions instead of running them.
+    dry_run: bool,
+}
+
+impl Tmux {
+    pub fn new(bin: PathBuf, server: Option<TmuxServer>, retries: u32, dry_run: bool) -> Self {
+        Self {
+            bin,
+            server,
+            retries,
+            dry_run,
+        }
+    }
+
+    /// Returns a tmux command for the configured server, so that sessions are always looked up
+    /// and created on the same one.
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.bin);
+        match &self.server {
+            Some(TmuxServer::Name(name)) => command.arg("-L").arg(name),
+            Some(TmuxServer::Socket(path)) => command.arg("-S").arg(path),
+            None => &mut command,
+        };
+
+        command
+    }
+
+    /// Runs tmux with `args`, describing what it's `doing` if it fails.
+    fn run<S: AsRef<OsStr>>(&self, args: &[S], doing: &str) -> Result<()> {
+        let status = run_command(self.command().args(args), self.dry_run)?;
+
+        check_status("tmux", status, doing)
+    }
+
+    /// Runs tmux with `args` like [`Tmux::run`], running it again after a delay that grows each
+    /// time if it fails with one of the [`TRANSIENT_ERRORS`], up to the configured number of
+    /// retries. Other failures fail right away.
+    fn run_with_retries(&self, args: &[&str], doing: &str) -> Result<()> {
+        if self.dry_run {
+            return self.run(args, doing);
+        }
+
+        let mut delay = RETRY_DELAY;
+        let mut retries = 0;
+        loop {
+            let output = self.command().args(args).output()?;
+            if output.status.success() {
+                return Ok(());
+            }
+
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stderr = stderr.trim();
+            if retries >= self.retries || !TRANSIENT_ERRORS.iter().any(|err| stderr.contains(err)) {
+                if stderr.is_empty() {
+                    return check_status("tmux", output.status, doing);
+                }
+                return Err(anyhow!("tmux failed {}: {}", doing, stderr));
+            }
+
+            status::debug(&format!(
+                "tmux failed {} with '{}', so retrying in {:?}",
+                doing, stderr, delay
+            ));
+            thread::sleep(delay);
+            delay *= 2;
+            retries += 1;
+        }
+    }
+}
+
+impl Multiplexer for Tmux {
+    fn name(&self) -> &'static str {
+        "tmux"
+    }
+
+    fn list_sessions(&self) -> Result<Vec<String>> {
+        let list_sessions = self
+            .command()
+            .arg("list-sessions")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let list_sessions_output = list_sessions.wait_with_output()?;
+        let stderr = String::from_utf8_lossy(&list_sessions_output.stderr);
+
+        let active_sessions = match list_sessions_output.status.code() {
+            Some(0) => String::from_utf8_lossy(&list_sessions_output.stdout),
+            // tmux fails when no server is running or the server has no sessions
+            Some(1) if NO_SESSIONS_ERRORS.iter().any(|err| stderr.contains(err)) => {
+                return Ok(Vec::new())
+            }
+            Some(code) if stderr.trim().is_empty() => {
+                return Err(anyhow!("tmux errored with code: {}.", code))
+            }
+            Some(code) => {
+                return Err(anyhow!(
+                    "tmux errored with code: {}: {}",
+                    code,
+                    stderr.trim()
+                ))
+            }
+            None => return Err(anyhow!("Nothing was returned by tmux.")),
+        };
+
+        let sessions = active_sessions
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(session_name, _)| session_name.to_string())
+            .collect::<Vec<_>>();
+        status::debug(&format!("tmux has the sessions {:?}", sessions));
+
+        Ok(sessions)
+    }
+
+    fn session_dir(&self, session_name: &str) -> Result<Option<PathBuf>> {
+        // Command: "tmux display-message -p -t {session_name} #{session_path}"
+        let display_message = self
+            .command()
+            .arg("display-message")
+            .arg("-p") // print to stdout
+            .arg("-t") // target session name
+            .arg(session_name)
+            .arg("#{session_path}")
+            .output()?;
+
+        check_status(
+            "tmux",
+            display_message.status,
+            "reading the session directory",
+        )?;
+
+        let dir = String::from_utf8_lossy(&display_message.stdout);
+        let dir = dir.trim_end_matches('\n');
+
+        Ok((!dir.is_empty()).then(|| PathBuf::from(dir)))
+    }
+
+    fn current_session(&self) -> Result<Option<String>> {
+        if env::var_os("TMUX").is_none() {
+            return Ok(None);
+        }
+
+        // Command: "tmux display-message -p #{session_name}"
+        let display_message = self
+            .command()
+            .arg("display-message")
+            .arg("-p") // print to stdout
+            .arg("#{session_name}")
+            .output()?;
+
+        check_status(
+            "tmux",
+            display_message.status,
+            "reading the current session",
+        )?;
+
+        let session = String::from_utf8_lossy(&display_message.stdout);
+        let session = session.trim_end_matches('\n');
+
+        Ok((!session.is_empty()).then(|| session.to_string()))
+    }
+
+    fn create_session(&self, session_name: &str, dir: &Path) -> Result<()> {
+        // Command: "tmux new -c {dir} -s {session_name} -d"
+        // Wait for the session to be created, otherwise switching to it can fail
+        let create_session_as_daemon = run_command(
+            self.command()
+                .arg("new-session") // create new session
+                .arg("-c") // change current working directory
+                .arg(dir)
+                .arg("-s") // new session name
+                .arg(session_name)
+                .arg("-d"), // initialize session in the background
+            self.dry_run,
+        )?;
+
+        check_status("tmux", create_session_as_daemon, "creating the session")
+    }
+
+    fn apply_layout(&self, session_name: &str, dir: &Path, layout: &Layout) -> Result<()> {
+        let dir = dir.as_os_str();
+        // The current window of the session, which is the one last created
+        let current_window = format!("{}:", session_name);
+        let doing = "applying the layout";
+
+        for (idx, window) in layout.windows.iter().enumerate() {
+            let name = window.name.as_deref();
+
+            if idx == 0 {
+                // The session starts with a window, which becomes the first one of the layout
+                if let Some(name) = name {
+                    // Command: "tmux rename-window -t {session_name}: {name}"
+                    self.run(&["rename-window", "-t", &current_window, name], doing)?;
+                }
+            } else {
+                // Command: "tmux new-window -t {session_name}: -c {dir} [-n {name}]"
+                let mut args = vec![
+                    OsStr::new("new-window"),
+                    OsStr::new("-t"),
+                    OsStr::new(&current_window),
+                    OsStr::new("-c"),
+                    dir,
+                ];
+                if let Some(name) = name {
+                    args.extend([OsStr::new("-n"), OsStr::new(name)]);
+                }
+                self.run(&args, doing)?;
+            }
+
+            if let Some(command) = &window.command {
+                self.send_command(&current_window, command)?;
+            }
+
+            for command in &window.panes {
+                // Command: "tmux split-window -t {session_name}: -c {dir}"
+                self.run(
+                    &[
+                        OsStr::new("split-window"),
+                        OsStr::new("-t"),
+                        OsStr::new(&current_window),
+                        OsStr::new("-c"),
+                        dir,
+                    ],
+                    doing,
+                )?;
+
+                if !command.is_empty() {
+                    self.send_command(&current_window, command)?;
+                }
+            }
+        }
+
+        // Command: "tmux select-window -t {session_name}:^"
+        self.run(
+            &["select-window", "-t", &format!("{}:^", session_name)],
+            doing,
+        )
+    }
+
+    fn kill_session(&self, session_name: &str) -> Result<()> {
+        // Command: "tmux kill-session -t {session_name}"
+        self.run(&["kill-session", "-t", session_name], "killing the session")
+    }
+
+    fn open_window(&self, dir: &Path, command: &str) -> Result<()> {
+        if env::var_os("TMUX").is_none() {
+            return Err(anyhow!("Can't open a window from outside of tmux."));
+        }
+
+        // Command: "tmux new-window -c {dir} {command}"
+        self.run(
+            &[
+                OsStr::new("new-window"),
+                OsStr::new("-c"),
+                dir.as_os_str(),
+                OsStr::new(command),
+            ],
+            "opening the window",
+        )
+    }
+
+    fn select_window(&self, session_name: &str, window_name: &str) -> Result<()> {
+        // Command: "tmux list-windows -t {session_name} -F #{window_name}"
+        let list_windows = self
+            .command()
+            .arg("list-windows")
+            .arg("-t") // target session name
+            .arg(session_name)
+            .arg("-F") // only print window names
+            .arg("#{window_name}")
+            .stderr(Stdio::null())
+            .output()?;
+
+        // A session that doesn't exist yet with `dry_run` has no windows either
+        let has_window = list_windows.status.success()
+            && String::from_utf8_lossy(&list_windows.stdout)
+                .lines()
+                .any(|name| name == window_name);
+        if !has_window {
+            status::debug(&format!(
+                "Session '{}' has no window named '{}', so it is left as it is",
+                session_name, window_name
+            ));
+            return Ok(());
+        }
+
+        // Command: "tmux select-window -t {session_name}:{window_name}"
+        self.run(
+            &[
+                "select-window",
+                "-t",
+                &format!("{}:{}", session_name, window_name),
+            ],
+            "selecting the window",
+        )
+    }
+
+    fn send_command(&self, session_name: &str, command: &str) -> Result<()> {
+        // Command: "tmux send-keys -t {session_name} {command} Enter"
+        let send_keys = run_command(
+            self.command()
+                .arg("send-keys") // type into a pane
+                .arg("-t") // target session name
+                .arg(session_name)
+                .arg(command)
+                .arg("Enter"), // run the command
+            self.dry_run,
+        )?;
+
+        check_status("tmux", send_keys, "sending the command")
+    }
+
+    fn attach_or_switch(&self, session_name: &str) -> Result<()> {
+        // Check if the user is currrently in a tmux session
+        let in_tmux = env::var("TMUX").is_ok();
+
+        if in_tmux {
+            status::debug("$TMUX is set, so the current client is switched");
+            status::print(&format!("Switching to session '{}'", session_name));
+
+            // Command: "tmux switch -t {session_name}"
+            return self
+                .run_with_retries(&["switch", "-t", session_name], "switching to the session");
+        }
+
+        status::debug("$TMUX isn't set, so the session is attached to");
+        status::print(&format!("Attaching to session '{}'", session_name));
+
+        // Attaching takes over the terminal, so whether the session can be found is checked first
+        if !self.dry_run {
+            // Command: "tmux has-session -t {session_name}"
+            self.run_with_retries(&["has-session", "-t", session_name], "finding the session")?;
+        }
+
+        // Command: "tmux attach -t {session_name}"
+        let attach = run_command(
+            self.command()
+                .arg("attach") // attach to session
+                .arg("-t") // target session name
+                .arg(session_name),
+            self.dry_run,
+        )?;
+
+        check_status("tmux", attach, "attaching to the session")
+    }
+}
+
+#[derive(Debug)]
+pub struct Zellij {
+    bin: PathBuf,
+    /// Whether to print the commands that change sessions instead of running them.
+    dry_run: bool,
+}
+
+impl Zellij {
+    pub fn new(bin: PathBuf, dry_run: bool) -> Self {
+        Self { bin, dry_run }
+    }
+}
+
+impl Multiplexer for Zellij {
+    fn name(&self) -> &'static str {
+        "zellij"
+    }
+
+    fn list_sessions(&self) -> Result<Vec<String>> {
+        // Command: "zellij list-sessions --short"
+        let list_sessions_output = Command::new(&self.bin)
+            .arg("list-sessions") // list sessions
+            .arg("--short") // only print session names
+            .output()?;
+
+        if !list_sessions_output.status.success() {
+            // Zellij errors when there are no active sessions
+            let stderr = String::from_utf8_lossy(&list_sessions_output.stderr);
+            if stderr.contains("No active zellij sessions") {
+                return Ok(Vec::new());
+            }
+
+            check_status("zellij", list_sessions_output.status, "listing sessions")?;
+        }
+
+        let sessions = String::from_utf8_lossy(&list_sessions_output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>();
+        status::debug(&format!("Zellij has the sessions {:?}", sessions));
+
+        Ok(sessions)
+    }
+
+    fn current_session(&self) -> Result<Option<String>> {
+        Ok(env::var("ZELLIJ_SESSION_NAME")
+            .ok()
+            .filter(|session| !session.is_empty()))
+    }
+
+    fn create_session(&self, session_name: &str, dir: &Path) -> Result<()> {
+        // Command: "zellij attach --create-background {session_name}" from `dir`
+        let create_session = run_command(
+            Command::new(&self.bin)
+                .current_dir(dir)
+                .arg("attach") // attach to session
+                .arg("--create-background") // create the session without attaching to it
+                .arg(session_name),
+            self.dry_run,
+        )?;
+
+        check_status("zellij", create_session, "creating the session")
+    }
+
+    fn kill_session(&self, session_name: &str) -> Result<()> {
+        // Command: "zellij kill-session {session_name}"
+        let kill_session = run_command(
+            Command::new(&self.bin)
+                .arg("kill-session")
+                .arg(session_name),
+            self.dry_run,
+        )?;
+
+        check_status("zellij", kill_session, "killing the session")
+    }
+
+    fn send_command(&self, session_name: &str, command: &str) -> Result<()> {
+        // Command: "zellij --session {session_name} action write-chars {command}"
+        let write_chars = run_command(
+            Command::new(&self.bin)
+                .arg("--session") // target session name
+                .arg(session_name)
+                .arg("action")
+                .arg("write-chars") // type into the focused pane
+                .arg(format!("{}\n", command)),
+            self.dry_run,
+        )?;
+
+        check_status("zellij", write_chars, "sending the command")
+    }
+
+    fn attach_or_switch(&self, session_name: &str) -> Result<()> {
+        if env::var_os("ZELLIJ").is_some() {
+            return Err(anyhow!(
+                "Can't switch to session '{}' from inside Zellij, detach first.",
+                session_name
+            ));
+        }
+
+        status::print(&format!("Attaching to session '{}'", session_name));
+
+        // Command: "zellij attach {session_name}"
+        let attach = run_command(
+            Command::new(&self.bin)
+                .arg("attach") // attach to session
+                .arg(session_name),
+            self.dry_run,
+        )?;
+
+        check_status("zellij", attach, "attaching to the session")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatted_commands_quote_arguments_that_need_it() {
+        let mut command = Command::new("tmux");
+        command
+            .args(["new-session", "-d", "-s", "my project", "-n", ""])
+            .args(["-F", "#{session_name}", "echo $HOME", "it's"])
+            .current_dir("/src/a b");
+
+        assert_eq!(
+            format_command(&command),
+            "cd '/src/a b' && tmux new-session -d -s 'my project' -n '' \
+            -F #{session_name} 'echo $HOME' 'it'\\''s'"
+        );
+        assert_eq!(
+            format_command(Command::new("/usr/bin/tmux").arg("attach")),
+            "/usr/bin/tmux attach"
+        );
+    }
+
+    #[test]
+    fn tmux_commands_are_sent_to_the_configured_server() {
+        let command =
+            |server| format_command(&Tmux::new("tmux".into(), server, 0, false).command());
+
+        assert_eq!(command(None), "tmux");
+        assert_eq!(
+            command(Some(TmuxServer::Name("work".to_string()))),
+            "tmux -L work"
+        );
+        assert_eq!(
+            command(Some(TmuxServer::Socket(PathBuf::from("/tmp/my socket")))),
+            "tmux -S '/tmp/my socket'"
+        );
+    }
+}
@@ -0,0 +1,1045 @@
+//! Searching the source directories for projects.
+
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, DirEntry, ReadDir};
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::config::{glob_matches, SrcDir};
+use crate::multiplexer::{session_name, SessionNameTemplate};
+use crate::status;
+
+/// How long scanning can take by default.
+pub const DEFAULT_SCAN_TIMEOUT: Duration = Duration::from_secs(2);
+/// How many directories can be read while scanning by default.
+pub const DEFAULT_MAX_DIRS: usize = 100_000;
+/// How many directories a source directory is estimated to have before warning about it by
+/// default.
+pub const DEFAULT_SCAN_WARNING: u64 = 10_000;
+
+/// The order projects are listed in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Most recently modified first
+    #[default]
+    Mtime,
+    /// Alphabetically by path
+    Name,
+    /// In the order they were found
+    None,
+}
+
+impl FromStr for SortOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        <Self as ValueEnum>::from_str(s, false).map_err(|_| {
+            anyhow!(
+                "Invalid sort order '{}', expected 'mtime', 'name' or 'none'.",
+                s
+            )
+        })
+    }
+}
+
+/// A project directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Project {
+    /// The *full* path to the project root directory. (Including the directory name itself)
+    inner: PathBuf,
+    /// The path of the source directory the project was found in, if known.
+    src_dir: Option<PathBuf>,
+}
+
+impl Project {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            inner: path,
+            src_dir: None,
+        }
+    }
+
+    /// Creates a project that was found in the source directory at `src_dir`.
+    pub fn with_src_dir(path: PathBuf, src_dir: PathBuf) -> Self {
+        Self {
+            inner: path,
+            src_dir: Some(src_dir),
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        if let Some(s) = self.inner.file_name() {
+            s.to_str()
+        } else {
+            None
+        }
+    }
+
+    pub fn full_path(&self) -> &Path {
+        self.inner.as_path()
+    }
+
+    pub fn src_dir(&self) -> Option<&Path> {
+        self.src_dir.as_deref()
+    }
+
+    /// Returns the name of the project's session, which is its `name()` with the characters tmux
+    /// doesn't allow in session names replaced by `replacement`.
+    ///
+    /// Names that aren't UTF-8 have their invalid characters replaced too.
+    pub fn session_name(&self, replacement: char) -> Option<String> {
+        self.session_name_from_template(&SessionNameTemplate::default(), None, replacement)
+    }
+
+    /// Returns the name of the project's session from a `template`, with the characters tmux
+    /// doesn't allow in session names replaced by `replacement`.
+    ///
+    /// `src_dir` is the source directory the project is in, which `{source}` is replaced with the
+    /// name of. Paths ending in `..` are named after the directory they resolve to, and the root
+    /// directory, which has no name, after its path with `/` replaced by `replacement`. `None` is
+    /// only returned if the session name would be empty.
+    pub fn session_name_from_template(
+        &self,
+        template: &SessionNameTemplate,
+        src_dir: Option<&Path>,
+        replacement: char,
+    ) -> Option<String> {
+        let resolved;
+        let path = if self.inner.file_name().is_some() {
+            self.inner.as_path()
+        } else {
+            resolved = fs::canonicalize(&self.inner).unwrap_or_else(|_| normalize(&self.inner));
+            resolved.as_path()
+        };
+
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy(),
+            None => Cow::from(
+                path.to_string_lossy()
+                    .replace(['/', '\\'], &replacement.to_string()),
+            ),
+        };
+        let parent = path
+            .parent()
+            .and_then(Path::file_name)
+            .map(|parent| parent.to_string_lossy())
+            .unwrap_or_default();
+        let source = src_dir
+            .and_then(Path::file_name)
+            .map(|source| source.to_string_lossy())
+            .unwrap_or_default();
+
+        // Templates are validated when they are created
+        let expanded = template
+            .expand(|placeholder| match placeholder {
+                "name" => &name,
+                "parent" => &parent,
+                _ => &source,
+            })
+            .ok()?;
+
+        Some(session_name(&expanded, replacement)).filter(|name| !name.is_empty())
+    }
+}
+
+/// Resolves the `.` and `..` components of `path` without reading the file system, for paths
+/// that don't exist.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            // The parent of the root is the root itself
+            Component::ParentDir if normalized.has_root() => {}
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// Controls which directories are searched for projects.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Whether to search hidden directories, which are skipped by default.
+    pub hidden: bool,
+    /// Whether the excluded directory names are matched ignoring case.
+    pub exclude_ignore_case: bool,
+    /// Whether to search symlinked directories, which are skipped by default.
+    pub follow_symlinks: bool,
+    /// Whether directories containing `.git` are projects whose contents aren't searched,
+    /// whatever their depth.
+    pub git_roots: bool,
+}
+
+/// The projects found in source directories.
+#[derive(Debug, Default)]
+pub struct Scan {
+    pub projects: Vec<Project>,
+    /// The directories whose contents decided which projects were found.
+    pub dirs: Vec<PathBuf>,
+    /// The directories that were skipped because they couldn't be read.
+    pub errors: Vec<ScanError>,
+    /// Whether scanning stopped early because its budget ran out.
+    pub truncated: bool,
+}
+
+impl Scan {
+    fn extend(&mut self, other: Scan) {
+        self.projects.extend(other.projects);
+        self.dirs.extend(other.dirs);
+        self.errors.extend(other.errors);
+        self.truncated |= other.truncated;
+    }
+}
+
+/// Limits how long scanning for projects can take, so that a too large depth doesn't make it
+/// seem like it hangs.
+#[derive(Debug)]
+pub struct ScanBudget {
+    deadline: Option<Instant>,
+    max_dirs: Option<usize>,
+    dirs_read: AtomicUsize,
+    exhausted: AtomicBool,
+}
+
+impl ScanBudget {
+    /// Creates a budget for a scan starting now, where `None` means no limit.
+    pub fn new(timeout: Option<Duration>, max_dirs: Option<usize>) -> Self {
+        Self {
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            max_dirs,
+            dirs_read: AtomicUsize::new(0),
+            exhausted: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns whether another directory can be read, marking the budget as exhausted if not.
+    fn try_read_dir(&self) -> bool {
+        if self.is_exhausted() {
+            return false;
+        }
+
+        let dirs_read = self.dirs_read.fetch_add(1, Ordering::Relaxed);
+        let exhausted = self.max_dirs.is_some_and(|max_dirs| dirs_read >= max_dirs)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline);
+        if exhausted {
+            self.exhausted.store(true, Ordering::Relaxed);
+        }
+
+        !exhausted
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+}
+
+/// A directory that couldn't be read while scanning for projects.
+#[derive(Debug)]
+pub struct ScanError {
+    pub path: PathBuf,
+    pub err: io::Error,
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to read '{}': {}", self.path.display(), self.err)
+    }
+}
+
+/// Finds the projects in each of the `src_dirs`, skipping the directories that can't be read.
+///
+/// Projects found more than once are only kept where they were first found.
+pub fn scan_projects(src_dirs: &[SrcDir], opts: &ScanOptions, budget: &ScanBudget) -> Result<Scan> {
+    let mut scan = Scan::default();
+
+    for src_dir in src_dirs {
+        if !budget.try_read_dir() {
+            break;
+        }
+
+        match fs::read_dir(&src_dir.path) {
+            Ok(dir) => {
+                let src_dir_scan = get_projects(dir, src_dir, opts, budget)?;
+                status::debug(&format!(
+                    "Found {} projects in '{}'",
+                    src_dir_scan.projects.len(),
+                    src_dir.path.display()
+                ));
+                scan.extend(src_dir_scan);
+            }
+            Err(err) => scan.errors.push(ScanError {
+                path: src_dir.path.clone(),
+                err,
+            }),
+        }
+    }
+
+    // Overlapping source directories, or symlinks to the same directory, find a project twice
+    let mut seen = HashSet::new();
+    scan.projects.retain(|project| {
+        seen.insert(
+            fs::canonicalize(project.full_path())
+                .unwrap_or_else(|_| project.full_path().to_path_buf()),
+        )
+    });
+
+    scan.truncated = budget.is_exhausted();
+
+    Ok(scan)
+}
+
+/// Finds the projects between the minimum and maximum depth below `src_dir`, where a depth of 0
+/// is the source directory itself.
+///
+/// If the source directory has markers, only directories containing one of them are projects
+/// and the directories that contain one aren't searched any further. With
+/// [`ScanOptions::git_roots`], the same goes for git repositories below the source directory,
+/// which are projects at any depth.
+fn get_projects(
+    src_dir: ReadDir,
+    src: &SrcDir,
+    opts: &ScanOptions,
+    budget: &ScanBudget,
+) -> Result<Scan> {
+    /// Adds `entry`, which is one directory below the source directory, to `res` if it's a
+    /// project and searches it for projects down to the maximum depth.
+    ///
+    /// The directories left to search are kept on a stack rather than searched recursively, so
+    /// that deep trees can't overflow the stack. They are popped in the order they were read in,
+    /// the same as searching each one before its next sibling.
+    ///
+    /// When following symlinks, `visited` holds the canonical paths of the directories already
+    /// searched so that a symlink to one of their parents isn't searched in a loop.
+    fn scan_subtree(
+        entry: &DirEntry,
+        src: &SrcDir,
+        opts: &ScanOptions,
+        visited: &mut HashSet<PathBuf>,
+        budget: &ScanBudget,
+        res: &mut Scan,
+    ) {
+        let children = visit(entry, 1, src, opts, visited, budget, res);
+        let mut stack = children
+            .into_iter()
+            .rev()
+            .map(|child| (child, 2))
+            .collect::<Vec<_>>();
+
+        while let Some((entry, depth)) = stack.pop() {
+            let children = visit(&entry, depth, src, opts, visited, budget, res);
+            stack.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+        }
+    }
+
+    /// Adds `entry`, which is `depth` directories below the source directory, to `res` if it's a
+    /// project and returns the entries to search next if the maximum depth hasn't been reached.
+    fn visit(
+        entry: &DirEntry,
+        depth: u8,
+        src: &SrcDir,
+        opts: &ScanOptions,
+        visited: &mut HashSet<PathBuf>,
+        budget: &ScanBudget,
+        res: &mut Scan,
+    ) -> Vec<DirEntry> {
+        if is_skipped(entry, src, opts) {
+            return Vec::new();
+        }
+
+        let is_symlink = entry
+            .file_type()
+            .is_ok_and(|file_type| file_type.is_symlink());
+        if is_symlink && !opts.follow_symlinks {
+            return Vec::new();
+        }
+
+        let path = entry.path();
+        // Files aren't projects and can't be searched, so they don't use up the budget. Unlike
+        // `entry.metadata()`, this follows symlinks
+        if !fs::metadata(&path).is_ok_and(|metadata| metadata.is_dir()) {
+            return Vec::new();
+        }
+
+        let has_marker = has_marker(&path, &src.markers);
+        let is_git_root = opts.git_roots && path.join(".git").exists();
+
+        if is_git_root || (depth >= src.min_depth && (src.markers.is_empty() || has_marker)) {
+            res.projects
+                .push(Project::with_src_dir(path.clone(), src.path.clone()));
+        }
+
+        if opts.follow_symlinks
+            && fs::canonicalize(&path).is_ok_and(|canonical| !visited.insert(canonical))
+        {
+            return Vec::new();
+        }
+
+        if depth < src.max_depth && !has_marker && !is_git_root && is_searched(&path, src) {
+            if !budget.try_read_dir() {
+                return Vec::new();
+            }
+
+            match fs::read_dir(&path) {
+                Ok(dir) => {
+                    res.dirs.push(path);
+                    return dir.map_while(Result::ok).collect();
+                }
+                Err(err) => res.errors.push(ScanError { path, err }),
+            }
+        } else if !src.markers.is_empty() || is_git_root {
+            // Adding a marker to the directory, or removing `.git`, changes whether it's a project
+            res.dirs.push(path);
+        }
+
+        Vec::new()
+    }
+
+    let mut scan = Scan {
+        dirs: vec![src.path.clone()],
+        ..Default::default()
+    };
+
+    // At a depth of 0 the source directory is a project itself, like a single pinned repository
+    let has_marker = has_marker(&src.path, &src.markers);
+    if src.min_depth == 0 && (src.markers.is_empty() || has_marker) {
+        scan.projects
+            .push(Project::with_src_dir(src.path.clone(), src.path.clone()));
+    }
+    if src.max_depth == 0 || has_marker {
+        return Ok(scan);
+    }
+
+    let entries = src_dir.map_while(Result::ok).collect::<Vec<_>>();
+    // Every cycle passes through a directory searched before it in the same subtree, so each
+    // subtree only has to track its own directories
+    let visited = fs::canonicalize(&src.path)
+        .into_iter()
+        .filter(|_| opts.follow_symlinks)
+        .collect::<HashSet<_>>();
+
+    if src.max_depth <= 1 {
+        let mut visited = visited;
+        for entry in &entries {
+            scan_subtree(entry, src, opts, &mut visited, budget, &mut scan);
+        }
+        return Ok(scan);
+    }
+
+    // The subtrees are independent, so they are split between worker threads and their projects
+    // put back in the order the subtrees were read in to keep the output deterministic
+    let next_entry = AtomicUsize::new(0);
+    let workers = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(entries.len());
+
+    let mut scanned_subtrees = thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|_| {
+                scope.spawn(|| -> Vec<(usize, Scan)> {
+                    let mut scanned = Vec::new();
+                    loop {
+                        let idx = next_entry.fetch_add(1, Ordering::Relaxed);
+                        let Some(entry) = entries.get(idx) else {
+                            return scanned;
+                        };
+
+                        let mut subtree_scan = Scan::default();
+                        let mut visited = visited.clone();
+                        scan_subtree(entry, src, opts, &mut visited, budget, &mut subtree_scan);
+                        scanned.push((idx, subtree_scan));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .map_err(|_| anyhow!("A thread panicked while scanning for projects."))
+            })
+            .collect::<Result<Vec<_>>>()
+    })?
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+
+    scanned_subtrees.sort_by_key(|(idx, _)| *idx);
+
+    for (_, subtree_scan) in scanned_subtrees {
+        scan.extend(subtree_scan);
+    }
+
+    Ok(scan)
+}
+
+/// Estimates how many directories are searched below `src_dir` from how many directories it
+/// contains itself, assuming every directory below it contains as many, without scanning it.
+pub fn estimate_dirs(src_dir: &SrcDir) -> u64 {
+    let Ok(dir) = fs::read_dir(&src_dir.path) else {
+        return 0;
+    };
+    let children = dir
+        .map_while(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .count() as u64;
+
+    children.saturating_pow(src_dir.max_depth.into())
+}
+
+pub fn sort_projects(projects: &mut [Project], order: SortOrder) {
+    match order {
+        SortOrder::Mtime => projects.sort_by_cached_key(|project| {
+            let modified = fs::metadata(project.full_path()).and_then(|m| m.modified());
+            Reverse(modified.unwrap_or(UNIX_EPOCH))
+        }),
+        SortOrder::Name => projects.sort_by(|a, b| a.full_path().cmp(b.full_path())),
+        SortOrder::None => {}
+    }
+}
+
+/// Sorts `projects` by their `scores`, highest first, followed by the projects without a score in
+/// the order they were in.
+pub fn rank_projects(projects: &mut [Project], scores: &HashMap<PathBuf, f64>) {
+    projects.sort_by(|a, b| {
+        let score = |project: &Project| scores.get(project.full_path()).copied();
+        match (score(a), score(b)) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (a, b) => Reverse(a.is_some()).cmp(&Reverse(b.is_some())),
+        }
+    });
+}
+
+/// Returns whether `entry` should be neither searched nor listed as a project.
+fn is_skipped(entry: &DirEntry, src: &SrcDir, opts: &ScanOptions) -> bool {
+    let file_name = entry.file_name();
+    if file_name.as_encoded_bytes().starts_with(b".") && !opts.hidden {
+        return true;
+    }
+
+    let Some(file_name) = file_name.to_str() else {
+        return false;
+    };
+
+    src.exclude.iter().any(|name| {
+        if opts.exclude_ignore_case {
+            name.to_lowercase() == file_name.to_lowercase()
+        } else {
+            name == file_name
+        }
+    })
+}
+
+/// Returns whether the directory at `path`, below the source directory `src`, is searched for
+/// projects, which it is if it matches the start of any of the `search_only` patterns.
+///
+/// A pattern shorter than the path only restricts the directories at its first levels, so that
+/// `rust` searches everything in `rust` and `*/backend` only searches `backend` one level down.
+fn is_searched(path: &Path, src: &SrcDir) -> bool {
+    if src.search_only.is_empty() {
+        return true;
+    }
+    let Ok(relative) = path.strip_prefix(&src.path) else {
+        return true;
+    };
+
+    src.search_only.iter().any(|pattern| {
+        pattern
+            .split('/')
+            .zip(relative.components())
+            .all(|(pattern, component)| {
+                component.as_os_str().to_str().is_some_and(|name| {
+                    glob_matches(
+                        &pattern.chars().collect::<Vec<_>>(),
+                        &name.chars().collect::<Vec<_>>(),
+                    )
+                })
+            })
+    })
+}
+
+/// Returns whether the directory at `path` contains any of the `markers`.
+fn has_marker(path: &Path, markers: &[String]) -> bool {
+    markers.iter().any(|marker| path.join(marker).exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_name_keeps_name() {
+        let project = Project::new(PathBuf::from("/home/user/src/example.com:8080"));
+
+        assert_eq!(project.name(), Some("example.com:8080"));
+        assert_eq!(
+            project.session_name('_').as_deref(),
+            Some("example_com_8080")
+        );
+        assert_eq!(
+            project.session_name('-').as_deref(),
+            Some("example-com-8080")
+        );
+    }
+
+    #[test]
+    fn session_name_ignores_trailing_slash() {
+        let project = Project::new(PathBuf::from("/home/user/src/api/"));
+
+        assert_eq!(project.name(), Some("api"));
+        assert_eq!(project.session_name('_').as_deref(), Some("api"));
+    }
+
+    #[test]
+    fn session_name_of_single_component_path() {
+        for path in ["api", "/api"] {
+            let project = Project::new(PathBuf::from(path));
+
+            assert_eq!(project.session_name('_').as_deref(), Some("api"));
+        }
+    }
+
+    #[test]
+    fn session_name_of_parent_dir_path_is_resolved() {
+        let project = Project::new(PathBuf::from("/nonexistent/user/src/.."));
+        assert_eq!(project.name(), None);
+        assert_eq!(project.session_name('_').as_deref(), Some("user"));
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("team/api")).unwrap();
+        let project = Project::new(dir.path().join("team/api/.."));
+        assert_eq!(project.session_name('_').as_deref(), Some("team"));
+        let template = SessionNameTemplate::new("{parent}-{name}").unwrap();
+        assert_eq!(
+            project.session_name_from_template(&template, None, '_'),
+            Project::new(dir.path().join("team")).session_name_from_template(&template, None, '_')
+        );
+
+        assert_eq!(
+            Project::new(PathBuf::from("/"))
+                .session_name('_')
+                .as_deref(),
+            Some("_")
+        );
+    }
+
+    #[test]
+    fn session_name_is_unchanged_without_disallowed_characters() {
+        let project = Project::new(PathBuf::from("/home/user/src/fp-rs"));
+
+        assert_eq!(project.session_name('_').as_deref(), Some("fp-rs"));
+    }
+
+    #[test]
+    fn session_name_from_template_expands_placeholders() {
+        let project = Project::new(PathBuf::from("/home/user/src/github.com/acme/api"));
+        let template = SessionNameTemplate::new("{source}/{parent}-{name}").unwrap();
+
+        assert_eq!(
+            project
+                .session_name_from_template(&template, Some(Path::new("/home/user/src")), '_')
+                .as_deref(),
+            Some("src/acme-api")
+        );
+    }
+
+    #[test]
+    fn session_name_from_template_sanitizes_expanded_name() {
+        let project = Project::new(PathBuf::from("/home/user/src/org.io/api"));
+        let template = SessionNameTemplate::new("{parent}:{name}").unwrap();
+
+        assert_eq!(
+            project
+                .session_name_from_template(&template, None, '_')
+                .as_deref(),
+            Some("org_io_api")
+        );
+    }
+
+    #[test]
+    fn session_name_template_rejects_unknown_placeholders() {
+        assert!(SessionNameTemplate::new("{org}-{name}").is_err());
+        assert!(SessionNameTemplate::new("{name").is_err());
+        assert!(SessionNameTemplate::new("fixed").is_ok());
+    }
+
+    #[test]
+    fn markers_find_projects_at_any_depth_in_range() {
+        let dir = tempfile::tempdir().unwrap();
+        for marker in ["shallow/.git", "group/deep/.git", "shallow/nested/.git"] {
+            fs::create_dir_all(dir.path().join(marker)).unwrap();
+        }
+        fs::create_dir_all(dir.path().join("group/unmarked")).unwrap();
+        let src_dir = SrcDir {
+            path: dir.path().to_path_buf(),
+            min_depth: 1,
+            max_depth: 2,
+            markers: vec![".git".to_string()],
+            ..Default::default()
+        };
+
+        let scan = scan_projects(
+            &[src_dir],
+            &ScanOptions::default(),
+            &ScanBudget::new(None, None),
+        )
+        .unwrap();
+
+        let mut projects = scan
+            .projects
+            .iter()
+            .map(|project| project.full_path().strip_prefix(dir.path()).unwrap())
+            .collect::<Vec<_>>();
+        projects.sort();
+        assert_eq!(projects, [Path::new("group/deep"), Path::new("shallow")]);
+    }
+
+    #[test]
+    fn finds_every_directory_in_depth_range() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in ["a/b/c/too-deep", "a/d", "e", ".hidden/f", "node_modules/g"] {
+            fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+        fs::write(dir.path().join("a/file"), "").unwrap();
+        let src_dir = SrcDir {
+            path: dir.path().to_path_buf(),
+            min_depth: 1,
+            max_depth: 3,
+            exclude: vec!["node_modules".to_string()],
+            ..Default::default()
+        };
+
+        let scan = scan_projects(
+            &[src_dir],
+            &ScanOptions::default(),
+            &ScanBudget::new(None, None),
+        )
+        .unwrap();
+
+        let mut projects = scan
+            .projects
+            .iter()
+            .map(|project| project.full_path().strip_prefix(dir.path()).unwrap())
+            .collect::<Vec<_>>();
+        projects.sort();
+        assert_eq!(projects, ["a", "a/b", "a/b/c", "a/d", "e"].map(Path::new));
+        // Each directory is searched before its next sibling, so its projects are listed together
+        let position = |path: &str| {
+            scan.projects
+                .iter()
+                .position(|project| project.full_path() == dir.path().join(path))
+                .unwrap()
+        };
+        let subtree = ["a", "a/b", "a/b/c", "a/d"].map(position);
+        assert_eq!(subtree.iter().max().unwrap() - subtree[0], 3);
+        assert_eq!(position("a/b") + 1, position("a/b/c"));
+    }
+
+    #[test]
+    fn files_dont_use_up_max_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("team/api")).unwrap();
+        for idx in 0..50 {
+            fs::write(dir.path().join(format!("notes-{}.txt", idx)), "").unwrap();
+        }
+        let src_dir = SrcDir {
+            path: dir.path().to_path_buf(),
+            min_depth: 2,
+            max_depth: 2,
+            ..Default::default()
+        };
+        let opts = ScanOptions {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+
+        // The source directory and `team` are the only directories read
+        let scan = scan_projects(&[src_dir], &opts, &ScanBudget::new(None, Some(2))).unwrap();
+
+        assert!(!scan.truncated);
+        assert!(scan.errors.is_empty());
+        assert_eq!(
+            scan.projects
+                .iter()
+                .map(Project::full_path)
+                .collect::<Vec<_>>(),
+            [dir.path().join("team/api")]
+        );
+    }
+
+    #[test]
+    fn deep_tree_scan_is_complete_and_deterministic() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut expected = Vec::new();
+        for a in 0..6 {
+            for b in 0..5 {
+                for c in 0..4 {
+                    let project = dir.path().join(format!("{}/{}/{}", a, b, c));
+                    fs::create_dir_all(&project).unwrap();
+                    expected.push(project);
+                }
+            }
+        }
+        let src_dir = SrcDir {
+            path: dir.path().to_path_buf(),
+            min_depth: 3,
+            max_depth: 3,
+            ..Default::default()
+        };
+        let scan = || {
+            scan_projects(
+                std::slice::from_ref(&src_dir),
+                &ScanOptions::default(),
+                &ScanBudget::new(None, None),
+            )
+            .unwrap()
+            .projects
+            .into_iter()
+            .map(|project| project.full_path().to_path_buf())
+            .collect::<Vec<_>>()
+        };
+
+        let first = scan();
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(sorted, expected);
+        // The subtrees are scanned by several threads, which mustn't change the order
+        for _ in 0..10 {
+            assert_eq!(scan(), first);
+        }
+    }
+
+    #[test]
+    fn search_only_prunes_unmatched_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in [
+            "rust/api",
+            "go/cli",
+            "notes/draft",
+            "work/backend/svc",
+            "work/web/app",
+        ] {
+            fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+        let src_dir = SrcDir {
+            path: dir.path().to_path_buf(),
+            min_depth: 2,
+            max_depth: 3,
+            search_only: vec![
+                "rust".to_string(),
+                "g?".to_string(),
+                "work/backend".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let scan = scan_projects(
+            &[src_dir],
+            &ScanOptions::default(),
+            &ScanBudget::new(None, None),
+        )
+        .unwrap();
+
+        let mut projects = scan
+            .projects
+            .iter()
+            .map(|project| project.full_path().strip_prefix(dir.path()).unwrap())
+            .collect::<Vec<_>>();
+        projects.sort();
+        assert_eq!(
+            projects,
+            [
+                "go/cli",
+                "rust/api",
+                "work/backend",
+                "work/backend/svc",
+                "work/web"
+            ]
+            .map(Path::new)
+        );
+    }
+
+    #[test]
+    fn overlapping_src_dirs_find_projects_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("group/project")).unwrap();
+        let src_dirs = [
+            SrcDir {
+                path: dir.path().join("group"),
+                min_depth: 1,
+                max_depth: 1,
+                ..Default::default()
+            },
+            SrcDir {
+                path: dir.path().to_path_buf(),
+                min_depth: 2,
+                max_depth: 2,
+                ..Default::default()
+            },
+            SrcDir {
+                path: dir.path().join("missing"),
+                min_depth: 1,
+                max_depth: 1,
+                ..Default::default()
+            },
+        ];
+
+        let scan = scan_projects(
+            &src_dirs,
+            &ScanOptions::default(),
+            &ScanBudget::new(None, None),
+        )
+        .unwrap();
+
+        assert_eq!(scan.projects.len(), 1);
+        assert_eq!(scan.projects[0].src_dir(), Some(src_dirs[0].path.as_path()));
+        assert_eq!(scan.errors.len(), 1);
+        assert_eq!(scan.errors[0].path, src_dirs[2].path);
+    }
+
+    #[test]
+    fn estimates_dirs_from_top_level() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a", "b", "c"] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        fs::write(dir.path().join("file"), "").unwrap();
+        let src_dir = SrcDir {
+            path: dir.path().to_path_buf(),
+            min_depth: 3,
+            max_depth: 3,
+            ..Default::default()
+        };
+
+        assert_eq!(estimate_dirs(&src_dir), 27);
+    }
+
+    #[test]
+    fn min_depth_skips_shallower_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("group/project/nested")).unwrap();
+        fs::create_dir(dir.path().join("loose")).unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+        let src_dir = SrcDir {
+            path: dir.path().to_path_buf(),
+            min_depth: 2,
+            max_depth: 2,
+            ..Default::default()
+        };
+
+        let scan = scan_projects(
+            &[src_dir],
+            &ScanOptions::default(),
+            &ScanBudget::new(None, None),
+        )
+        .unwrap();
+
+        assert_eq!(
+            scan.projects,
+            [Project::with_src_dir(
+                dir.path().join("group/project"),
+                dir.path().to_path_buf()
+            )]
+        );
+    }
+
+    #[test]
+    fn depth_zero_is_the_src_dir_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("child")).unwrap();
+        let src_dir = SrcDir {
+            path: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let scan = scan_projects(
+            &[src_dir],
+            &ScanOptions::default(),
+            &ScanBudget::new(None, None),
+        )
+        .unwrap();
+
+        assert_eq!(
+            scan.projects,
+            [Project::with_src_dir(
+                dir.path().to_path_buf(),
+                dir.path().to_path_buf()
+            )]
+        );
+    }
+
+    #[test]
+    fn git_roots_stop_the_search() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in ["repo/.git", "repo/vendor/lib", "group/app", "group/web"] {
+            fs::create_dir_all(dir.path().join("src").join(path)).unwrap();
+        }
+        let src_dir = SrcDir {
+            path: dir.path().join("src"),
+            min_depth: 2,
+            max_depth: 2,
+            ..Default::default()
+        };
+        let opts = ScanOptions {
+            git_roots: true,
+            ..Default::default()
+        };
+
+        let scan = scan_projects(&[src_dir], &opts, &ScanBudget::new(None, None)).unwrap();
+
+        let mut projects = scan
+            .projects
+            .iter()
+            .map(|project| project.full_path().strip_prefix(dir.path()).unwrap())
+            .collect::<Vec<_>>();
+        projects.sort();
+        assert_eq!(
+            projects,
+            [
+                Path::new("src/group/app"),
+                Path::new("src/group/web"),
+                Path::new("src/repo")
+            ]
+        );
+    }
+
+    #[test]
+    fn ranks_unknown_projects_last_in_order() {
+        let mut projects = ["/src/a", "/src/b", "/src/c", "/src/d"]
+            .map(|path| Project::new(PathBuf::from(path)))
+            .to_vec();
+        let scores = HashMap::from([
+            (PathBuf::from("/src/c"), 1.0),
+            (PathBuf::from("/src/d"), 4.0),
+        ]);
+
+        rank_projects(&mut projects, &scores);
+
+        let paths = projects
+            .iter()
+            .map(|project| project.full_path().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["/src/d", "/src/c", "/src/a", "/src/b"]);
+    }
+}
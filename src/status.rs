@@ -0,0 +1,77 @@
+//! Printing what is being done to stderr, leaving stdout for output other programs read.
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static COLOR: AtomicBool = AtomicBool::new(false);
+static BELL: AtomicBool = AtomicBool::new(false);
+static DEBUG: AtomicBool = AtomicBool::new(false);
+
+/// Starts the messages that stand out from the others, so that they are noticed without color.
+const NOTICE_PREFIX: &str = "==> ";
+
+/// Sets how status messages are printed for the rest of the run.
+///
+/// Messages are colored when stderr is a terminal and [`color_allowed`]. With `debug`,
+/// [`debug`] messages are printed too, even if quiet.
+pub fn init(quiet: bool, debug: bool) {
+    let color = io::stderr().is_terminal() && color_allowed();
+
+    QUIET.store(quiet, Ordering::Relaxed);
+    COLOR.store(color, Ordering::Relaxed);
+    DEBUG.store(debug, Ordering::Relaxed);
+}
+
+/// Returns whether anything may be colored, which setting `$NO_COLOR` disables as per
+/// <https://no-color.org>. Output that isn't shown in a terminal shouldn't be colored either.
+pub fn color_allowed() -> bool {
+    env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+}
+
+/// Sets whether [`notice`] rings the terminal bell, which is read from the config after the
+/// messages that come before it have been printed.
+pub fn set_bell(bell: bool) {
+    BELL.store(bell, Ordering::Relaxed);
+}
+
+/// Prints a status `message`, unless quiet.
+pub fn print(message: &str) {
+    if !QUIET.load(Ordering::Relaxed) {
+        eprintln!("{}", message);
+    }
+}
+
+/// Prints a `message` explaining why something was done, only when debugging.
+pub fn debug(message: &str) {
+    if DEBUG.load(Ordering::Relaxed) {
+        eprintln!("debug: {}", message);
+    }
+}
+
+/// Prints a status `message` that stands out, unless quiet, for things that are easy to miss,
+/// like a new session being created instead of an existing one being reused. Rings the terminal
+/// bell too if enabled.
+pub fn notice(message: &str) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let bell = if BELL.load(Ordering::Relaxed) {
+        "\x07"
+    } else {
+        ""
+    };
+    let mut stderr = io::stderr().lock();
+    // Failing to print a status message isn't worth failing for
+    let _ = if COLOR.load(Ordering::Relaxed) {
+        writeln!(
+            stderr,
+            "\x1b[1;32m{}{}\x1b[0m{}",
+            NOTICE_PREFIX, message, bell
+        )
+    } else {
+        writeln!(stderr, "{}{}{}", NOTICE_PREFIX, message, bell)
+    };
+}
@@ -0,0 +1,51 @@
+//! Reading how often and how recently directories were visited, as tracked by zoxide.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::path_from_bytes;
+
+/// Returns the score zoxide gives each directory it knows about, or `None` if zoxide can't be run.
+pub fn query_scores(zoxide_bin: &Path) -> Option<HashMap<PathBuf, f64>> {
+    let output = Command::new(zoxide_bin)
+        .args(["query", "--list", "--score"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(parse_scores(&output.stdout))
+}
+
+/// Parses the `<score> <path>` lines printed by `zoxide query --list --score`, skipping the ones
+/// that can't be parsed.
+fn parse_scores(output: &[u8]) -> HashMap<PathBuf, f64> {
+    output
+        .split(|&byte| byte == b'\n')
+        .filter_map(|line| {
+            let line = line.trim_ascii_start();
+            let space = line.iter().position(|&byte| byte == b' ')?;
+            let (score, path) = line.split_at(space);
+            let score = std::str::from_utf8(score).ok()?.parse().ok()?;
+            Some((path_from_bytes(&path[1..]), score))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scores() {
+        let scores = parse_scores(b"  12.5 /home/user/src/a\n   0.3 /home/user/src/b c\n\n");
+
+        assert_eq!(scores.len(), 2);
+        assert_eq!(scores[Path::new("/home/user/src/a")], 12.5);
+        assert_eq!(scores[Path::new("/home/user/src/b c")], 0.3);
+    }
+}
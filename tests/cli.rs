@@ -0,0 +1,381 @@
+//! Runs the binary with fake `fzf` and `tmux` scripts, which record how they were run and print
+//! canned output, to test how the projects are passed to fzf and which tmux commands are run.
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+/// Records its arguments and stdin, then prints `$FAKE_FZF_OUTPUT` and exits with
+/// `$FAKE_FZF_CODE`.
+const FAKE_FZF: &str = r#"#!/bin/sh
+printf '%s\n' "$*" >> "$FAKE_DIR/fzf_args"
+cat > "$FAKE_DIR/fzf_stdin"
+printf '%s' "$FAKE_FZF_OUTPUT"
+exit "${FAKE_FZF_CODE:-0}"
+"#;
+
+/// Records its arguments, printing `$FAKE_TMUX_SESSIONS` when listing the sessions. Creating a
+/// session takes a moment, records when it's done and exits with `$FAKE_TMUX_NEW_SESSION_CODE`.
+const FAKE_TMUX: &str = r#"#!/bin/sh
+printf '%s\n' "$*" >> "$FAKE_DIR/tmux_args"
+case "$1" in
+list-sessions) printf '%s' "$FAKE_TMUX_SESSIONS" ;;
+new-session)
+    sleep 0.2
+    printf 'created\n' >> "$FAKE_DIR/tmux_args"
+    exit "${FAKE_TMUX_NEW_SESSION_CODE:-0}"
+    ;;
+esac
+exit 0
+"#;
+
+/// A home directory with projects in `~/src`, a config listing it and the fake binaries.
+struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    fn new(projects: &[&str]) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        for project in projects {
+            fs::create_dir_all(dir.path().join("src").join(project)).unwrap();
+        }
+        fs::write(
+            dir.path().join("find_project.conf"),
+            format!("{} 1\n", dir.path().join("src").display()),
+        )
+        .unwrap();
+
+        let fixture = Fixture { dir };
+        fixture.write_script("fzf", FAKE_FZF);
+        fixture.write_script("tmux", FAKE_TMUX);
+        fixture
+    }
+
+    fn write_script(&self, name: &str, contents: &str) {
+        let path = self.dir.path().join(name);
+        fs::write(&path, contents).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    fn project(&self, name: &str) -> PathBuf {
+        self.path("src").join(name)
+    }
+
+    /// Returns the command running the binary outside of tmux, without anything from the
+    /// environment the tests are run in.
+    fn command(&self) -> Command {
+        let mut command = self.command_without_config();
+        command.arg("--config").arg(self.path("find_project.conf"));
+        command
+    }
+
+    /// Returns the command reading the config from `~/.config`, where there is none.
+    fn command_without_config(&self) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_fp-rs"));
+        command
+            .env_clear()
+            .env("PATH", "/usr/bin:/bin")
+            .env("HOME", self.dir.path())
+            .env("FAKE_DIR", self.dir.path())
+            .env("FIND_PROJECT_FZF", self.path("fzf"))
+            .env("FIND_PROJECT_TMUX", self.path("tmux"));
+        command
+    }
+
+    /// Returns what was recorded in the `name` file by the fake binaries.
+    fn recorded(&self, name: &str) -> String {
+        fs::read_to_string(self.path(name)).unwrap_or_default()
+    }
+}
+
+/// Returns the path in each line of the list passed to fzf, the last field of the line.
+fn listed_paths(fzf_stdin: &str) -> Vec<PathBuf> {
+    let mut paths = fzf_stdin
+        .lines()
+        .map(|line| PathBuf::from(line.rsplit('\t').next().unwrap()))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+/// Returns fzf's output selecting `path`.
+fn selected(path: &Path) -> String {
+    format!("{}\n", path.display())
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn pipes_projects_to_fzf() {
+    let fixture = Fixture::new(&["alpha", "beta"]);
+
+    let output = fixture
+        .command()
+        .env("FAKE_FZF_CODE", "130")
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        listed_paths(&fixture.recorded("fzf_stdin")),
+        [fixture.project("alpha"), fixture.project("beta")]
+    );
+    assert_eq!(output.status.code(), Some(130));
+}
+
+#[test]
+fn creates_and_attaches_to_new_session() {
+    let fixture = Fixture::new(&["alpha", "beta"]);
+
+    let output = fixture
+        .command()
+        .env("FAKE_FZF_OUTPUT", selected(&fixture.project("beta")))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let tmux_args = fixture.recorded("tmux_args");
+    assert!(
+        tmux_args.contains(&format!(
+            "new-session -c {} -s beta -d\n",
+            fixture.project("beta").display()
+        )),
+        "{}",
+        tmux_args
+    );
+    assert!(tmux_args.ends_with("attach -t beta\n"), "{}", tmux_args);
+}
+
+#[test]
+fn switches_to_active_session_inside_tmux() {
+    let fixture = Fixture::new(&["alpha", "beta"]);
+
+    let output = fixture
+        .command()
+        .env("TMUX", "/tmp/tmux-1000/default,1,0")
+        .env("FAKE_TMUX_SESSIONS", "beta: 1 windows\n")
+        .env("FAKE_FZF_OUTPUT", selected(&fixture.project("beta")))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let tmux_args = fixture.recorded("tmux_args");
+    assert!(!tmux_args.contains("new-session"), "{}", tmux_args);
+    assert!(tmux_args.ends_with("switch -t beta\n"), "{}", tmux_args);
+}
+
+#[test]
+fn switches_to_new_session_after_creating_it() {
+    let fixture = Fixture::new(&["alpha", "beta"]);
+
+    let output = fixture
+        .command()
+        .env("TMUX", "/tmp/tmux-1000/default,1,0")
+        .env("FAKE_FZF_OUTPUT", selected(&fixture.project("beta")))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let tmux_args = fixture.recorded("tmux_args");
+    assert!(
+        tmux_args.ends_with(&format!(
+            "new-session -c {} -s beta -d\ncreated\nswitch -t beta\n",
+            fixture.project("beta").display()
+        )),
+        "{}",
+        tmux_args
+    );
+}
+
+#[test]
+fn exits_with_tmux_code_when_creating_session_fails() {
+    let fixture = Fixture::new(&["alpha"]);
+
+    let output = fixture
+        .command()
+        .env("TMUX", "/tmp/tmux-1000/default,1,0")
+        .env("FAKE_TMUX_NEW_SESSION_CODE", "5")
+        .env("FAKE_FZF_OUTPUT", selected(&fixture.project("alpha")))
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("tmux failed creating the session with code: 5."),
+        "{}",
+        stderr(&output)
+    );
+    assert!(fixture.recorded("tmux_args").ends_with("created\n"));
+}
+
+#[test]
+fn attach_only_doesnt_create_sessions() {
+    let fixture = Fixture::new(&["alpha", "beta"]);
+
+    let output = fixture
+        .command()
+        .arg("--attach-only")
+        .env("FAKE_TMUX_SESSIONS", "alpha: 1 windows\n")
+        .env("FAKE_FZF_OUTPUT", selected(&fixture.project("beta")))
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("--attach-only"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!fixture.recorded("tmux_args").contains("new-session"));
+}
+
+#[test]
+fn exits_with_fzf_failures() {
+    let fixture = Fixture::new(&["alpha"]);
+
+    // fzf exits with 1 when nothing matches the query
+    let output = fixture
+        .command()
+        .env("FAKE_FZF_CODE", "1")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+
+    // Nothing is printed when fzf exits without a selection
+    let output = fixture.command().output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert!(!fixture.recorded("tmux_args").contains("new-session"));
+}
+
+#[test]
+fn reports_fzf_failures_without_panicking() {
+    let fixture = Fixture::new(&["alpha"]);
+
+    // Found and executable, but running it fails
+    fixture.write_script("fzf", "#!/nonexistent/sh\n");
+    let output = fixture.command().output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains(&format!(
+            "Failed to run `{}`",
+            fixture.path("fzf").display()
+        )),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!stderr(&output).contains("panicked"), "{}", stderr(&output));
+
+    // Exits without reading the projects
+    fixture.write_script("fzf", "#!/bin/sh\nexit 130\n");
+    let output = fixture.command().output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert!(!stderr(&output).contains("panicked"), "{}", stderr(&output));
+}
+
+#[test]
+fn forwards_select_1_and_exit_0_to_fzf() {
+    let fixture = Fixture::new(&["alpha", "beta"]);
+
+    let output = fixture
+        .command()
+        .args(["--query", "alp", "--select-1", "--exit-0"])
+        .env("FAKE_FZF_OUTPUT", selected(&fixture.project("alpha")))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let fzf_args = fixture.recorded("fzf_args");
+    assert!(
+        fzf_args.contains("--query alp --select-1 --exit-0"),
+        "{}",
+        fzf_args
+    );
+    assert!(fixture.recorded("tmux_args").ends_with("attach -t alpha\n"));
+
+    // fzf exits with 1 without opening when `--exit-0` applies
+    let output = fixture
+        .command()
+        .args(["--query", "gamma", "--select-1", "--exit-0"])
+        .env("FAKE_FZF_CODE", "1")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("No project matched your query."));
+}
+
+#[test]
+fn dry_run_doesnt_record_history() {
+    let fixture = Fixture::new(&["alpha", "beta"]);
+    let history = fixture.path(".cache/find_project/history");
+
+    let output = fixture
+        .command()
+        .arg("--dry-run")
+        .env("FAKE_FZF_OUTPUT", selected(&fixture.project("beta")))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!history.exists());
+
+    let output = fixture
+        .command()
+        .env("FAKE_FZF_OUTPUT", selected(&fixture.project("beta")))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(history.exists());
+}
+
+#[test]
+fn lists_cached_projects_without_scanning() {
+    let fixture = Fixture::new(&["alpha"]);
+    let list_cached = ["--list", "--list-cached", "--format", "tsv"];
+
+    // Nothing is printed until a scan has filled the cache
+    let output = fixture.command().args(list_cached).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(output.stdout.is_empty());
+    assert!(!fixture.path(".cache").exists());
+
+    let output = fixture.command().arg("--list").output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = fixture.command().args(list_cached).output().unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("alpha\t{}\n", fixture.project("alpha").display())
+    );
+
+    // The config isn't created to complete a project name
+    let output = fixture
+        .command_without_config()
+        .args(list_cached)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(output.stdout.is_empty());
+    assert!(!fixture.path(".config").exists());
+}
+
+#[test]
+fn exits_when_binary_is_missing() {
+    let fixture = Fixture::new(&["alpha"]);
+
+    let output = fixture
+        .command()
+        .env("FIND_PROJECT_FZF", fixture.path("missing"))
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(fixture.recorded("fzf_stdin").is_empty());
+}